@@ -118,8 +118,9 @@ class MongoConnector:
                     "yawnCount": { "$sum": { "$cond": [{ "$eq": ["$eventType", "YAWN_DETECTED"] }, 1, 0] } },
                     "distractionCount": { "$sum": { "$cond": [{ "$eq": ["$eventType", "DISTRACTION_STARTED"] }, 1, 0] } },
                     "drowsinessCount": { "$sum": { "$cond": [{ "$eq": ["$eventType", "DROWSINESS_STARTED"] }, 1, 0] } },
-                    "totalDistractionMs": { "$sum": { "$cond": [{ "$eq": ["$eventType", "DISTRACTION_STARTED"] }, "$payload.previousStateDurationMs", 0] } },
-                    "totalDrowsinessMs": { "$sum": { "$cond": [{ "$eq": ["$eventType", "DROWSINESS_STARTED"] }, "$payload.previousStateDurationMs", 0] } }
+                    # 각 상태의 지속 시간은 해당 상태가 끝날 때 발행되는 *_ENDED 이벤트의 durationMs를 합산합니다.
+                    "totalDistractionMs": { "$sum": { "$cond": [{ "$eq": ["$eventType", "DISTRACTION_ENDED"] }, "$payload.durationMs", 0] } },
+                    "totalDrowsinessMs": { "$sum": { "$cond": [{ "$eq": ["$eventType", "DROWSINESS_ENDED"] }, "$payload.durationMs", 0] } }
                 }
             },
             {
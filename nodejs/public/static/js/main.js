@@ -29,10 +29,15 @@ let isVideoPlaying = false;
 let latestLandmarks = [];
 
 // WebSocket 관련 변수 및 세션 ID
+// 웹소켓 서버는 서명된 접속 토큰을 요구합니다. 연결할 때마다 이 페이지의 서버(/api/token)에서 새 토큰을 받아옵니다.
+const TOKEN_URL = '/api/token';
 const WEBSOCKET_URL = `wss://${window.location.hostname}/ws`;
 let websocket;
 const SESSION_ID = crypto.randomUUID();
-const USER_ID = "1";
+const PROTOCOL_VERSION = 1; // 서버와 협상할 메시지 프로토콜 버전
+const LANDMARK_FRAME = 0x01; // 바이너리 랜드마크 프레임의 종류 바이트
+let useBinaryFrames = false; // 서버가 binaryFrames 기능을 수락했는지 여부
+let isSessionStarted = false; // 서버가 'start'를 확인했는지 여부 (그 전에는 분석 데이터를 보내지 않음)
 
 // 상태 추적 변수
 let isPaused = false;
@@ -114,8 +119,13 @@ function onResults(results) {
     }
 
     latestLandmarks = results.multiFaceLandmarks[0] || [];
+    if (!isSessionStarted) return; // 서버는 세션 시작 전의 분석 데이터를 거부합니다.
 
     if (latestLandmarks.length > 0) {
+        if (useBinaryFrames) {
+            sendLandmarkFrame(latestLandmarks);
+            return;
+        }
         const keyLandmarks = KEY_LANDMARK_INDICES.map(index => {
             const landmark = latestLandmarks[index];
             return { index, x: parseFloat(landmark.x.toFixed(4)), y: parseFloat(landmark.y.toFixed(4)), z: parseFloat(landmark.z.toFixed(4)) };
@@ -128,7 +138,7 @@ function onResults(results) {
 
 
 let lastProcessTime = 0;
-const processInterval = 1000;
+const processInterval = 1000; // 이 간격으로는 깜빡임을 잴 수 없어 서버가 BLINK_STATS를 보내지 않습니다. (평균 150ms 이내 간격이 필요)
 
 async function mainLoop(currentTime) {
     requestAnimationFrame(mainLoop);
@@ -193,10 +203,27 @@ async function initializeMediaPipe() {
     console.log("🟢 MediaPipe 모델 초기화 완료.");
 }
 
-function connectWebSocket() {
+// 웹소켓 접속 토큰을 받아옵니다. 사용자 ID는 서버가 정해 토큰에 담으므로, 메시지에는 userId를 적지 않습니다.
+async function fetchAuthToken() {
+    const response = await fetch(TOKEN_URL, { credentials: 'same-origin', cache: 'no-store' });
+    if (!response.ok) throw new Error(`HTTP ${response.status}`);
+    return (await response.json()).token;
+}
+
+async function connectWebSocket() {
     console.log(`🟡 WebSocket 연결 시도: ${WEBSOCKET_URL}`);
     statusElement.textContent = "실시간 분석 서버에 연결 중...";
-    websocket = new WebSocket(WEBSOCKET_URL);
+    let token;
+    try {
+        token = await fetchAuthToken();
+    } catch (error) {
+        console.error('🔴 접속 토큰 발급 실패, 5초 후 다시 시도합니다:', error);
+        statusElement.textContent = "서버에 연결할 수 없습니다. 재연결 중...";
+        setTimeout(connectWebSocket, 5000);
+        return;
+    }
+    // 브라우저 WebSocket API는 임의의 헤더를 보낼 수 없으므로, 토큰은 'bearer' 서브프로토콜과 함께 전달합니다.
+    websocket = new WebSocket(WEBSOCKET_URL, ['bearer', token]);
 
     websocket.onopen = () => {
         console.log('✅ WebSocket 연결 성공.');
@@ -209,25 +236,22 @@ function connectWebSocket() {
             }
         }, 5000);
 
-        sendEvent('start', { userAgent: navigator.userAgent });
+        // 프로토콜 버전과 받을 메시지 종류를 알리고, 서버의 welcome 응답을 받은 뒤 세션을 시작합니다.
+        useBinaryFrames = false;
+        isSessionStarted = false;
+        sendEvent('hello', { protocolVersion: PROTOCOL_VERSION, capabilities: ['state', 'score', 'binaryFrames'], landmarkIndices: KEY_LANDMARK_INDICES });
     };
 
     websocket.onmessage = (event) => {
-        const alarmMessage = event.data; 
-        console.log(`🔔 서버로부터 메시지 수신: ${alarmMessage}`);
-        statusElement.textContent = `🚨 ${alarmMessage}`;
-        addWarningToList(alarmMessage); // 여기에 경고가 추가될 것
-        playWarningBeep();
-
-        Toastify({
-            text: `🚨 ${alarmMessage}`,
-            duration: 3000,
-            newWindow: true,
-            close: true,
-            gravity: "top", 
-            position: "right", 
-            stopOnFocus: true,
-        }).showToast();
+        console.log(`🔔 서버로부터 메시지 수신: ${event.data}`);
+        let message;
+        try {
+            message = JSON.parse(event.data);
+        } catch (e) {
+            // 구조화되지 않은 예전 형식의 메시지는 알람 문구로 취급합니다.
+            message = { type: 'alarm', severity: 'warning', message: event.data };
+        }
+        handleServerMessage(message);
     };
 
     websocket.onclose = () => {
@@ -243,12 +267,84 @@ function connectWebSocket() {
     };
 }
 
+// 서버 메시지의 type에 따라 알람 표시, 상태 갱신 등을 처리합니다.
+function handleServerMessage(message) {
+    switch (message.type) {
+        case 'welcome':
+            console.log(`🤝 프로토콜 협상 완료: v${message.protocolVersion} (${message.capabilities.join(', ')})`);
+            useBinaryFrames = message.capabilities.includes('binaryFrames');
+            sendEvent('start', { userAgent: navigator.userAgent, language: navigator.language });
+            break;
+        case 'alarm':
+            showAlarm(message);
+            break;
+        case 'state':
+            if (message.state === 'FOCUSED') {
+                statusElement.textContent = "집중 분석 중";
+            }
+            break;
+        case 'score':
+            console.log(`📈 집중도 점수: ${message.score.toFixed(1)}`);
+            break;
+        case 'ack':
+            console.log(`✅ 서버 확인: ${message.eventType}`);
+            if (message.eventType === 'start') isSessionStarted = true;
+            break;
+        case 'error':
+            console.error(`🔴 서버 에러 (${message.code}${message.eventType ? `, ${message.eventType}` : ''}): ${message.message}`);
+            break;
+        default:
+            console.warn("알 수 없는 서버 메시지:", message);
+    }
+}
+
+// 알람 심각도에 따른 토스트 배경색
+const SEVERITY_COLORS = {
+    info: "linear-gradient(to right, #2563eb, #3b82f6)",
+    warning: "linear-gradient(to right, #d97706, #f59e0b)",
+    critical: "linear-gradient(to right, #b91c1c, #ef4444)",
+};
+
+function showAlarm(alarm) {
+    const alarmMessage = alarm.message;
+    statusElement.textContent = `🚨 ${alarmMessage}`;
+    addWarningToList(alarmMessage); // 여기에 경고가 추가될 것
+    if (alarm.severity !== 'info') {
+        playWarningBeep();
+    }
+
+    Toastify({
+        text: `🚨 ${alarmMessage}`,
+        duration: 3000,
+        newWindow: true,
+        close: true,
+        gravity: "top", 
+        position: "right", 
+        stopOnFocus: true,
+        style: { background: SEVERITY_COLORS[alarm.severity] || SEVERITY_COLORS.warning },
+    }).showToast();
+}
+
 function sendEvent(eventType, payload) {
     if (!websocket || websocket.readyState !== WebSocket.OPEN) return;
-    const message = { sessionId: SESSION_ID, userId: USER_ID, timestamp: new Date().toISOString(), eventType: eventType, payload: payload };
+    const message = { sessionId: SESSION_ID, timestamp: new Date().toISOString(), eventType: eventType, payload: payload };
     websocket.send(JSON.stringify(message));
 }
 
+// 핵심 랜드마크의 x, y, z를 KEY_LANDMARK_INDICES 순서대로 little-endian f32로 담아 바이너리 프레임으로 보냅니다.
+function sendLandmarkFrame(landmarks) {
+    if (!websocket || websocket.readyState !== WebSocket.OPEN) return;
+    const view = new DataView(new ArrayBuffer(1 + KEY_LANDMARK_INDICES.length * 12));
+    view.setUint8(0, LANDMARK_FRAME);
+    KEY_LANDMARK_INDICES.forEach((index, i) => {
+        const landmark = landmarks[index];
+        view.setFloat32(1 + i * 12, landmark.x, true);
+        view.setFloat32(5 + i * 12, landmark.y, true);
+        view.setFloat32(9 + i * 12, landmark.z, true);
+    });
+    websocket.send(view.buffer);
+}
+
 function addWarningToList(message) {
     console.log("addWarningToList 호출됨. 메시지:", message); // 디버깅용 로그
     if (!warningLog) {
@@ -2,6 +2,7 @@ const express = require('express');
 const router = express.Router();
 const path = require('path');
 const redis = require('redis');
+const crypto = require('crypto'); // 접속 토큰(HS256 JWT)과 사용자 쿠키에 서명합니다.
 
 // Redis 클라이언트 생성 및 연결
 const redisClient = redis.createClient({
@@ -17,6 +18,58 @@ redisClient.on('connect', () => console.log('🟢 Redis Client Connected'));
 })();
 
 
+// --- 웹소켓 접속 토큰 발급 ---
+// 웹소켓 서버는 같은 키(AUTH_JWT_SECRET)로 서명된 토큰만 받고, 토큰의 sub를 세션의 사용자 ID로 사용합니다.
+// 사용자 ID는 클라이언트가 정하지 않고, 이 서버가 브라우저마다 만들어 서명된 쿠키로 기억합니다.
+const AUTH_JWT_SECRET = process.env.AUTH_JWT_SECRET;
+const AUTH_JWT_ISSUER = process.env.AUTH_JWT_ISSUER;     // 웹소켓 서버의 AUTH_JWT_ISSUER와 같게 맞춥니다. (선택)
+const AUTH_JWT_AUDIENCE = process.env.AUTH_JWT_AUDIENCE; // 웹소켓 서버의 AUTH_JWT_AUDIENCE와 같게 맞춥니다. (선택)
+const AUTH_TOKEN_TTL_SECS = parseInt(process.env.AUTH_TOKEN_TTL_SECS || '300', 10); // 토큰은 연결할 때만 검사하므로 짧게 둡니다.
+const USER_COOKIE = 'attention_uid';
+const USER_COOKIE_MAX_AGE_MS = 365 * 24 * 60 * 60 * 1000;
+
+if (!AUTH_JWT_SECRET) {
+    console.log('🔴 AUTH_JWT_SECRET이 없어 웹소켓 접속 토큰을 발급하지 않습니다.');
+}
+
+const sign = (data) => crypto.createHmac('sha256', AUTH_JWT_SECRET).update(data).digest('base64url');
+
+// 사용자 ID를 sub로 담은 HS256 토큰을 만듭니다.
+function issueToken(userId) {
+    const now = Math.floor(Date.now() / 1000);
+    const claims = { sub: userId, iat: now, exp: now + AUTH_TOKEN_TTL_SECS };
+    if (AUTH_JWT_ISSUER) claims.iss = AUTH_JWT_ISSUER;
+    if (AUTH_JWT_AUDIENCE) claims.aud = AUTH_JWT_AUDIENCE;
+    const encode = (value) => Buffer.from(JSON.stringify(value)).toString('base64url');
+    const unsigned = `${encode({ alg: 'HS256', typ: 'JWT' })}.${encode(claims)}`;
+    return `${unsigned}.${sign(unsigned)}`;
+}
+
+// 요청의 사용자 쿠키(<사용자 ID>.<서명>)를 검증하여 사용자 ID를 돌려줍니다. 없거나 서명이 맞지 않으면 null입니다.
+function userIdFromCookie(req) {
+    const cookie = (req.headers.cookie || '').split(';').map((c) => c.trim()).find((c) => c.startsWith(`${USER_COOKIE}=`));
+    if (!cookie) return null;
+    const [userId, signature] = decodeURIComponent(cookie.slice(USER_COOKIE.length + 1)).split('.');
+    if (!userId || !signature) return null;
+    const expected = Buffer.from(sign(`uid:${userId}`));
+    const actual = Buffer.from(signature);
+    return expected.length === actual.length && crypto.timingSafeEqual(expected, actual) ? userId : null;
+}
+
+router.get('/api/token', (req, res) => {
+    res.set('Cache-Control', 'no-store');
+    if (!AUTH_JWT_SECRET) {
+        return res.status(503).json({ status: 'error', message: '접속 토큰을 발급할 수 없습니다.' });
+    }
+    let userId = userIdFromCookie(req);
+    if (!userId) {
+        userId = crypto.randomUUID();
+        res.cookie(USER_COOKIE, `${userId}.${sign(`uid:${userId}`)}`, { httpOnly: true, sameSite: 'strict', secure: req.secure, maxAge: USER_COOKIE_MAX_AGE_MS });
+    }
+    res.json({ token: issueToken(userId), expiresIn: AUTH_TOKEN_TTL_SECS });
+});
+
+
 // --- 페이지 라우팅 ---
 router.get('/', (req, res) => {
     res.sendFile(path.join(__dirname, '..', 'public', 'index.html'));
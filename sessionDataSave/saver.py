@@ -10,6 +10,18 @@ import sys
 REDIS_HOST = os.getenv('REDIS_HOST', 'localhost')
 REDIS_PORT = int(os.getenv('REDIS_PORT', 6379))
 
+# 웹소켓 서버의 이벤트 출력 방식(EVENT_OUTPUT_MODE)과 같게 맞춥니다. ("pubsub" 또는 "streams")
+# streams 방식에서는 컨슈머 그룹으로 읽고, MongoDB에 저장한 이벤트만 확인(XACK)합니다. 저장에 실패한 이벤트는 재시작 후 다시 읽습니다.
+EVENT_OUTPUT_MODE = os.getenv('EVENT_OUTPUT_MODE', 'pubsub')
+STREAM_GROUP = os.getenv('STREAM_GROUP', 'saver')
+# 컨슈머 이름은 재시작해도 바뀌지 않아야 확인하지 못한 이벤트를 이어서 처리합니다. (호스트 이름은 파드가 재시작될 때마다 바뀝니다)
+STREAM_CONSUMER = os.getenv('STREAM_CONSUMER', 'saver-1')
+# 시작할 때 이 시간(ms) 이상 확인되지 않은 다른 컨슈머의 이벤트를 가져와(XAUTOCLAIM) 처리합니다. (이름이 바뀌었거나 사라진 컨슈머의 이벤트)
+STREAM_CLAIM_IDLE_MS = int(os.getenv('STREAM_CLAIM_IDLE_MS', 60000))
+STREAM_RETRY_INITIAL_SECS = 0.5 # 저장에 실패한 이벤트를 다시 읽기 전의 첫 대기 시간 (실패할수록 2배씩, 최대 STREAM_RETRY_MAX_SECS)
+STREAM_RETRY_MAX_SECS = 30
+EVENTS_CHANNEL = 'attention-meaningful-events' # 이제 모든 이벤트는 이 단일 채널(또는 스트림)을 통해 들어옵니다.
+
 # MongoDB 접속 정보를 환경 변수에서 읽어옵니다.
 MONGO_HOST = os.getenv('MONGO_HOST')
 MONGO_PORT = int(os.getenv('MONGO_PORT'))
@@ -63,32 +75,96 @@ def main():
         print(f"🔴 MongoDB 연결 실패: {e}. 프로그램을 종료합니다.")
         return
 
-    # Redis 채널 구독
+    if EVENT_OUTPUT_MODE == 'streams':
+        consume_stream(redis_client, collection)
+    else:
+        consume_pubsub(redis_client, collection)
+
+
+def consume_pubsub(redis_client, collection):
+    """Redis 채널을 구독하여, 수신되는 메시지를 MongoDB에 저장합니다."""
     pubsub = redis_client.pubsub()
-    CHANNELS = ['attention-meaningful-events'] # 이제 모든 이벤트는 이 단일 채널을 통해 들어옵니다.
-    pubsub.subscribe(*CHANNELS)
-    print(f"📢 다음 채널을 구독합니다: {CHANNELS}")
+    pubsub.subscribe(EVENTS_CHANNEL)
+    print(f"📢 다음 채널을 구독합니다: {EVENTS_CHANNEL}")
     print("--- 데이터 수신 대기 중... ---")
 
     # 메시지를 계속해서 기다리고 MongoDB에 저장하는 무한 루프
     for message in pubsub.listen():
         if message['type'] != 'message':
             continue
+        save_event(collection, message['data'])
+
+
+def consume_stream(redis_client, collection):
+    """컨슈머 그룹으로 Redis 스트림을 읽어 MongoDB에 저장하고, 저장한 이벤트를 확인(XACK)합니다."""
+    try:
+        redis_client.xgroup_create(EVENTS_CHANNEL, STREAM_GROUP, id='0', mkstream=True)
+    except redis.exceptions.ResponseError as e:
+        if 'BUSYGROUP' not in str(e):
+            raise
+    print(f"📢 다음 스트림을 읽습니다: {EVENTS_CHANNEL} (group: {STREAM_GROUP}, consumer: {STREAM_CONSUMER})")
+    claim_idle_entries(redis_client)
+    print("--- 데이터 수신 대기 중... ---")
+
+    # 먼저 지난번에 읽고 확인하지 못한 이벤트('0')를 다시 처리한 뒤, 새 이벤트('>')를 기다립니다.
+    # 저장에 실패한 이벤트가 있으면 잠시 기다렸다가 확인하지 못한 이벤트('0')부터 다시 읽습니다. ('0'은 기다리지 않고 바로 돌아오므로)
+    last_id = '0'
+    retry_delay = STREAM_RETRY_INITIAL_SECS
+    while True:
+        entries = redis_client.xreadgroup(STREAM_GROUP, STREAM_CONSUMER, {EVENTS_CHANNEL: last_id}, count=100, block=5000)
+        messages = entries[0][1] if entries else []
+        if last_id == '0' and not messages:
+            last_id = '>'
+            continue
+        failed = False
+        for entry_id, fields in messages:
+            if save_event(collection, fields.get('data', '')):
+                redis_client.xack(EVENTS_CHANNEL, STREAM_GROUP, entry_id)
+            else:
+                failed = True
+        if failed:
+            print(f"⏳ 저장하지 못한 이벤트가 있어 {retry_delay:g}초 뒤에 다시 읽습니다.")
+            time.sleep(retry_delay)
+            retry_delay = min(retry_delay * 2, STREAM_RETRY_MAX_SECS)
+            last_id = '0'
+        else:
+            retry_delay = STREAM_RETRY_INITIAL_SECS
+
+
+def claim_idle_entries(redis_client):
+    """오래 확인되지 않은 다른 컨슈머의 이벤트를 이 컨슈머로 가져옵니다. 가져온 이벤트는 확인하지 못한 이벤트('0')로 다시 읽힙니다."""
+    cursor = '0-0'
+    claimed = 0
+    while True:
+        response = redis_client.xautoclaim(EVENTS_CHANNEL, STREAM_GROUP, STREAM_CONSUMER, STREAM_CLAIM_IDLE_MS, start_id=cursor, count=100)
+        cursor = response[0]
+        claimed += len(response[1])
+        if cursor == '0-0':
+            break
+    if claimed:
+        print(f"📥 다른 컨슈머가 확인하지 못한 이벤트 {claimed}개를 가져왔습니다.")
+
+
+def save_event(collection, raw):
+    """이벤트 JSON 하나를 MongoDB에 저장합니다. 저장에 성공하면 True를 반환합니다."""
+    try:
+        # 수신된 데이터(JSON 문자열)를 Python 딕셔너리로 파싱
+        data = json.loads(raw)
+
+        # ✨ 파일에 쓰는 대신, MongoDB에 데이터를 삽입(insert)합니다.
+        insert_result = collection.insert_one(data)
+
+        session_id = data.get('sessionId', 'N/A')
+        print(f"✅ 데이터 저장 완료 -> [Session: {session_id}, InsertedID: {insert_result.inserted_id}] -> MongoDB")
+        return True
 
-        try:
-            # 수신된 데이터(JSON 문자열)를 Python 딕셔너리로 파싱
-            data = json.loads(message['data'])
-            
-            # ✨ 파일에 쓰는 대신, MongoDB에 데이터를 삽입(insert)합니다.
-            insert_result = collection.insert_one(data)
-            
-            session_id = data.get('sessionId', 'N/A')
-            print(f"✅ 데이터 저장 완료 -> [Session: {session_id}, InsertedID: {insert_result.inserted_id}] -> MongoDB")
-
-        except json.JSONDecodeError as e:
-            print(f"🔴 JSON 파싱 에러: {e}, 원본: {message['data']}")
-        except Exception as e:
-            print(f"🔴 에러: MongoDB 저장 중 문제 발생 -> {e}")
+    except json.JSONDecodeError as e:
+        # 형식이 잘못된 이벤트는 다시 읽어도 저장할 수 없으므로 처리한 것으로 봅니다.
+        print(f"🔴 JSON 파싱 에러: {e}, 원본: {raw}")
+        return True
+    except Exception as e:
+        print(f"🔴 에러: MongoDB 저장 중 문제 발생 -> {e}")
+        return False
 
 
 if __name__ == "__main__":
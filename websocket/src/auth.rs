@@ -0,0 +1,154 @@
+// --- 웹소켓 연결 인증 ---
+// 웹소켓 핸드셰이크 요청에 담긴 서명된 토큰(HMAC JWT)을 검증하여, 토큰이 없거나 잘못된 연결은 업그레이드 전에 거부합니다.
+// 토큰은 쿼리 파라미터(`?token=...`) 또는 `Sec-WebSocket-Protocol: bearer, <토큰>` 헤더로 받을 수 있습니다. (브라우저 WebSocket API는 임의의 헤더를 보낼 수 없음)
+// 서명 키는 외부 서비스 없이 서버 설정(환경 변수 또는 파일)에서 읽어오므로, 오프라인 환경에서도 같은 키로 토큰을 만들어 시험할 수 있습니다.
+use crate::config::AuthConfig;
+use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation}; // JWT 서명/만료 검증
+use serde::Deserialize; // 토큰의 클레임(claim)을 Rust 구조체로 변환합니다.
+use std::fs; // 서명 키를 파일(예: 쿠버네티스 Secret 마운트)에서 읽기 위해 사용합니다.
+use tokio_tungstenite::tungstenite::handshake::server::Request;
+
+// 브라우저가 토큰을 Sec-WebSocket-Protocol 헤더로 보낼 때, 토큰 앞에 함께 보내는 서브프로토콜 이름입니다. 서버는 이 이름을 응답에 그대로 돌려줍니다.
+pub const BEARER_SUBPROTOCOL: &str = "bearer";
+
+// 검증에 성공한 토큰의 주인입니다.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthenticatedUser {
+    pub user_id: String, // 토큰의 sub 클레임. 클라이언트 메시지의 userId 대신 이 값을 사용합니다.
+}
+
+// 토큰에서 읽는 클레임입니다. (exp는 jsonwebtoken이 직접 검증합니다.)
+#[derive(Deserialize, Debug)]
+struct Claims { sub: String }
+
+// 핸드셰이크 토큰을 검증하는 인증기입니다.
+pub struct Authenticator {
+    key: DecodingKey,
+    validation: Validation,
+}
+
+impl Authenticator {
+    // 서버 설정의 인증 항목으로 인증기를 만듭니다.
+    // - jwt_secret 또는 jwt_secret_file: HMAC 서명 키 (필수)
+    // - issuer, audience: 지정하면 토큰의 iss/aud 클레임도 검증합니다.
+    // - disabled = true: 로컬 개발용으로 인증을 끕니다. 이때는 Ok(None)을 반환합니다.
+    pub fn from_config(config: &AuthConfig) -> Result<Option<Self>, String> {
+        if config.disabled { return Ok(None); }
+        let secret = match (&config.jwt_secret, &config.jwt_secret_file) {
+            (Some(secret), _) => secret.clone(),
+            (None, Some(path)) => fs::read_to_string(path).map_err(|e| format!("AUTH_JWT_SECRET_FILE({}) 읽기 실패: {}", path.display(), e))?.trim().to_string(),
+            _ => return Err("AUTH_JWT_SECRET 또는 AUTH_JWT_SECRET_FILE이 필요합니다. (인증 없이 실행하려면 AUTH_DISABLED=true)".to_string()),
+        };
+        if secret.is_empty() { return Err("인증 서명 키가 비어 있습니다.".to_string()); }
+        Ok(Some(Authenticator::new(secret.as_bytes(), config.issuer.as_deref(), config.audience.as_deref())))
+    }
+
+    // HMAC 서명 키와 (선택) 발급자/대상으로 인증기를 만듭니다. HS256/HS384/HS512 서명을 받습니다.
+    pub fn new(secret: &[u8], issuer: Option<&str>, audience: Option<&str>) -> Self {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.algorithms = vec![Algorithm::HS256, Algorithm::HS384, Algorithm::HS512];
+        // 발급자/대상을 지정했으면 그 클레임이 빠진 토큰도 받지 않습니다.
+        let mut required = vec!["exp", "sub"];
+        if let Some(issuer) = issuer { validation.set_issuer(&[issuer]); required.push("iss"); }
+        match audience { Some(audience) => { validation.set_audience(&[audience]); required.push("aud"); }, None => validation.validate_aud = false }
+        validation.set_required_spec_claims(&required);
+        Authenticator { key: DecodingKey::from_secret(secret), validation }
+    }
+
+    // 토큰의 서명, 만료 시각 등을 검증하고 토큰의 주인을 반환합니다.
+    pub fn verify(&self, token: &str) -> Result<AuthenticatedUser, String> {
+        let claims = decode::<Claims>(token, &self.key, &self.validation).map_err(|e| e.to_string())?.claims;
+        if claims.sub.is_empty() { return Err("empty sub claim".to_string()); }
+        Ok(AuthenticatedUser { user_id: claims.sub })
+    }
+}
+
+// 핸드셰이크 요청에서 토큰을 꺼냅니다. 두 번째 값은 헤더로 받은 경우 응답에 돌려줘야 하는 서브프로토콜입니다.
+pub fn token_from_request(request: &Request) -> Option<(String, Option<&'static str>)> {
+    let from_query = request.uri().query().and_then(|query| {
+        query.split('&').find_map(|pair| pair.strip_prefix("token=")).map(str::to_string)
+    });
+    if let Some(token) = from_query.filter(|token| !token.is_empty()) { return Some((token, None)); }
+    let protocols = request.headers().get("sec-websocket-protocol")?.to_str().ok()?;
+    let mut protocols = protocols.split(',').map(str::trim);
+    protocols.find(|p| *p == BEARER_SUBPROTOCOL)?;
+    protocols.next().filter(|token| !token.is_empty()).map(|token| (token.to_string(), Some(BEARER_SUBPROTOCOL)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use serde_json::{json, Value};
+
+    const SECRET: &[u8] = b"test-secret";
+
+    fn now() -> i64 { chrono::Utc::now().timestamp() }
+
+    fn token(claims: Value, secret: &[u8]) -> String {
+        encode(&Header::default(), &claims, &EncodingKey::from_secret(secret)).unwrap()
+    }
+
+    fn request(uri: &str, protocols: Option<&str>) -> Request {
+        let mut builder = Request::builder().uri(uri);
+        if let Some(protocols) = protocols { builder = builder.header("Sec-WebSocket-Protocol", protocols); }
+        builder.body(()).unwrap()
+    }
+
+    #[test]
+    fn accepts_valid_token() {
+        let authenticator = Authenticator::new(SECRET, None, None);
+        let user = authenticator.verify(&token(json!({ "sub": "user-1", "exp": now() + 60 }), SECRET)).unwrap();
+        assert_eq!(user, AuthenticatedUser { user_id: "user-1".to_string() });
+    }
+
+    #[test]
+    fn rejects_expired_token() {
+        let authenticator = Authenticator::new(SECRET, None, None);
+        assert!(authenticator.verify(&token(json!({ "sub": "user-1", "exp": now() - 3600 }), SECRET)).is_err());
+        assert!(authenticator.verify(&token(json!({ "sub": "user-1" }), SECRET)).is_err()); // exp가 없는 토큰도 받지 않습니다.
+    }
+
+    #[test]
+    fn rejects_token_signed_with_other_key() {
+        let authenticator = Authenticator::new(SECRET, None, None);
+        assert!(authenticator.verify(&token(json!({ "sub": "user-1", "exp": now() + 60 }), b"other-secret")).is_err());
+        assert!(authenticator.verify("not-a-token").is_err());
+    }
+
+    #[test]
+    fn checks_issuer_and_audience_when_configured() {
+        let authenticator = Authenticator::new(SECRET, Some("attention-app"), Some("attention-websocket"));
+        let claims = |iss: &str, aud: &str| json!({ "sub": "user-1", "exp": now() + 60, "iss": iss, "aud": aud });
+        assert!(authenticator.verify(&token(claims("attention-app", "attention-websocket"), SECRET)).is_ok());
+        assert!(authenticator.verify(&token(claims("someone-else", "attention-websocket"), SECRET)).is_err());
+        assert!(authenticator.verify(&token(claims("attention-app", "someone-else"), SECRET)).is_err());
+        assert!(authenticator.verify(&token(json!({ "sub": "user-1", "exp": now() + 60 }), SECRET)).is_err());
+    }
+
+    #[test]
+    fn rejects_empty_subject() {
+        let authenticator = Authenticator::new(SECRET, None, None);
+        assert_eq!(authenticator.verify(&token(json!({ "sub": "", "exp": now() + 60 }), SECRET)), Err("empty sub claim".to_string()));
+    }
+
+    #[test]
+    fn reads_token_from_query() {
+        assert_eq!(token_from_request(&request("/ws?foo=1&token=abc", None)), Some(("abc".to_string(), None)));
+        // 쿼리의 토큰이 헤더보다 우선합니다.
+        assert_eq!(token_from_request(&request("/ws?token=abc", Some("bearer, xyz"))), Some(("abc".to_string(), None)));
+    }
+
+    #[test]
+    fn reads_token_from_bearer_subprotocol() {
+        assert_eq!(token_from_request(&request("/ws", Some("bearer, xyz"))), Some(("xyz".to_string(), Some(BEARER_SUBPROTOCOL))));
+        assert_eq!(token_from_request(&request("/ws?token=", Some("bearer,xyz"))), Some(("xyz".to_string(), Some(BEARER_SUBPROTOCOL))));
+    }
+
+    #[test]
+    fn missing_token() {
+        assert_eq!(token_from_request(&request("/ws", None)), None);
+        assert_eq!(token_from_request(&request("/ws", Some("chat, xyz"))), None);
+        assert_eq!(token_from_request(&request("/ws", Some("bearer"))), None);
+    }
+}
@@ -0,0 +1,157 @@
+// --- 사용자별 보정(Calibration) ---
+// 세션 시작 직후 일정 시간 동안 EAR/MAR/Yaw 값을 모아 그 사용자만의 기준값(baseline)을 만들고,
+// 설정된 임계값을 이 기준값에 맞춰 옮깁니다. (눈 모양, 안경, 카메라 각도 차이를 보정)
+// 임계값 자체(진입/해제 값, 고개 회전 허용 범위)는 항상 설정에서 가져오므로, 설정을 바꾸면 보정된 세션에도 그대로 적용됩니다.
+use crate::engine::Thresholds;
+use crate::features::FrameFeatures;
+use serde::{Deserialize, Serialize}; // 보정 결과를 이벤트로 발행하고, 다음 세션에서 다시 읽어오기 위해 사용합니다.
+use std::time::Duration; // 보정 수집 시간 설정에 사용합니다.
+
+// 보정 단계의 동작과, 설정된 임계값이 가정하는 평균적인 사용자의 기준값입니다.
+#[derive(Debug, Clone, Copy)]
+pub struct CalibrationConfig {
+    pub duration: Duration,    // 기준값을 수집하는 시간
+    pub min_samples: usize,    // 보정을 완료하기 위해 필요한 최소 프레임 수
+    pub reference_ear: f64,    // 설정된 EAR 임계값이 가정하는 눈을 뜬 EAR (사용자의 EAR 기준값에 비례해 임계값을 옮깁니다.)
+    pub reference_mar: f64,    // 설정된 MAR 임계값이 가정하는 입을 다문 MAR (사용자의 MAR 기준값과의 차이만큼 임계값을 옮깁니다.)
+}
+
+impl Default for CalibrationConfig {
+    fn default() -> Self {
+        // 평균적인 사용자는 EAR 약 0.28, 입을 다문 MAR 약 0.1입니다.
+        CalibrationConfig { duration: Duration::from_secs(10), min_samples: 5, reference_ear: 0.28, reference_mar: 0.1 }
+    }
+}
+
+// 보정 단계에서 계산한 사용자의 기준값입니다. CALIBRATION_COMPLETED 이벤트로 발행되고, 다음 세션에서 재사용됩니다.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Calibration {
+    pub ear_mean: f64,
+    pub ear_std: f64,
+    pub mar_mean: f64,
+    pub mar_std: f64,
+    pub yaw_mean: f64,
+    pub yaw_std: f64,
+    #[serde(default)] // Pitch 보정이 추가되기 전에 저장된 보정 결과도 읽을 수 있도록 기본값(0)을 허용합니다.
+    pub pitch_mean: f64,
+    #[serde(default)]
+    pub pitch_std: f64,
+    pub samples: usize,
+}
+
+impl Calibration {
+    // 설정된 임계값을 이 사용자의 기준값에 맞춰 옮깁니다.
+    // EAR/MAR 임계값은 기준값만큼 옮기고, 고개 방향은 기준 방향을 중심으로 삼습니다. (yaw/pitch 진입/해제 값은 이미 중심 기준이므로 그대로 씁니다.)
+    pub fn thresholds(&self, configured: &Thresholds, config: &CalibrationConfig) -> Thresholds {
+        let ear_scale = if config.reference_ear > 0.0 { self.ear_mean / config.reference_ear } else { 1.0 };
+        let mar_shift = self.mar_mean - config.reference_mar;
+        Thresholds {
+            ear_enter: configured.ear_enter * ear_scale,
+            ear_exit: configured.ear_exit * ear_scale,
+            mar: configured.mar + mar_shift,
+            yaw_center: self.yaw_mean,
+            pitch_center: self.pitch_mean,
+            ..*configured
+        }
+    }
+}
+
+// 보정 단계 동안 프레임 특징 값을 모으는 수집기입니다.
+#[derive(Debug, Clone)]
+pub struct CalibrationCollector {
+    started_ms: u64,
+    ears: Vec<f64>,
+    mars: Vec<f64>,
+    yaws: Vec<f64>,
+    pitches: Vec<f64>,
+}
+
+impl CalibrationCollector {
+    pub fn new(started_ms: u64) -> Self {
+        CalibrationCollector { started_ms, ears: Vec::new(), mars: Vec::new(), yaws: Vec::new(), pitches: Vec::new() }
+    }
+
+    // 한 프레임의 특징 값을 기록합니다. 랜드마크 누락으로 EAR이 0이 된 프레임은 기준값을 왜곡하므로 버립니다.
+    pub fn add(&mut self, features: &FrameFeatures) {
+        if features.ear_left <= 0.0 || features.ear_right <= 0.0 { return; }
+        self.ears.push((features.ear_left + features.ear_right) / 2.0);
+        self.mars.push(features.mar);
+        self.yaws.push(features.head_yaw);
+        if let Some(pose) = features.pose { self.pitches.push(pose.pitch); }
+    }
+
+    // 수집 시간과 최소 프레임 수를 모두 채웠으면 보정 결과를 계산해 반환합니다.
+    pub fn finish(&self, now_ms: u64, config: &CalibrationConfig) -> Option<Calibration> {
+        let elapsed = Duration::from_millis(now_ms.saturating_sub(self.started_ms));
+        if elapsed < config.duration || self.ears.len() < config.min_samples { return None; }
+        let (ear_mean, ear_std) = mean_and_std(&self.ears);
+        let (mar_mean, mar_std) = mean_and_std(&self.mars);
+        let (yaw_mean, yaw_std) = mean_and_std(&self.yaws);
+        let (pitch_mean, pitch_std) = mean_and_std(&self.pitches);
+        Some(Calibration { ear_mean, ear_std, mar_mean, mar_std, yaw_mean, yaw_std, pitch_mean, pitch_std, samples: self.ears.len() })
+    }
+}
+
+// 값 목록의 평균과 표준편차를 계산하는 함수입니다.
+fn mean_and_std(values: &[f64]) -> (f64, f64) {
+    if values.is_empty() { return (0.0, 0.0); }
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+    (mean, variance.sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn features(ear: f64, mar: f64, yaw: f64) -> FrameFeatures {
+        FrameFeatures { ear_left: ear, ear_right: ear, mar, head_yaw: yaw, pose: None }
+    }
+
+    fn calibration(ear_mean: f64, mar_mean: f64, yaw_mean: f64, pitch_mean: f64) -> Calibration {
+        Calibration { ear_mean, ear_std: 0.0, mar_mean, mar_std: 0.0, yaw_mean, yaw_std: 0.0, pitch_mean, pitch_std: 0.0, samples: 10 }
+    }
+
+    #[test]
+    fn collector_needs_duration_and_min_samples() {
+        let config = CalibrationConfig::default();
+        let mut collector = CalibrationCollector::new(0);
+        for _ in 0..4 { collector.add(&features(0.3, 0.1, 0.0)); }
+        assert_eq!(collector.finish(20_000, &config), None); // 시간은 지났지만 프레임이 부족합니다.
+        collector.add(&features(0.3, 0.1, 0.0));
+        assert_eq!(collector.finish(9_999, &config), None);  // 프레임은 충분하지만 시간이 부족합니다.
+        assert!(collector.finish(10_000, &config).is_some());
+    }
+
+    #[test]
+    fn collector_computes_mean_and_std_and_skips_missing_eyes() {
+        let mut collector = CalibrationCollector::new(0);
+        for (ear, mar, yaw) in [(0.2, 0.1, -0.1), (0.4, 0.3, 0.1), (0.0, 0.9, 0.9), (0.3, 0.2, 0.0)] { collector.add(&features(ear, mar, yaw)); }
+        let config = CalibrationConfig { min_samples: 3, ..CalibrationConfig::default() };
+        let calibration = collector.finish(10_000, &config).unwrap();
+        assert_eq!(calibration.samples, 3); // EAR이 0인 프레임(랜드마크 누락)은 버립니다.
+        assert!((calibration.ear_mean - 0.3).abs() < 1e-9);
+        assert!((calibration.ear_std - (0.02f64 / 3.0).sqrt()).abs() < 1e-9);
+        assert!((calibration.mar_mean - 0.2).abs() < 1e-9);
+        assert!(calibration.yaw_mean.abs() < 1e-9);
+    }
+
+    #[test]
+    fn average_user_keeps_configured_thresholds() {
+        let configured = Thresholds::default();
+        assert_eq!(calibration(0.28, 0.1, 0.0, 0.0).thresholds(&configured, &CalibrationConfig::default()), configured);
+    }
+
+    #[test]
+    fn thresholds_move_with_baseline_and_keep_configured_margins() {
+        let configured = Thresholds { yaw_enter: 0.5, yaw_exit: 0.4, pitch_enter: 30.0, pitch_exit: 25.0, ..Thresholds::default() };
+        let thresholds = calibration(0.35, 0.2, 0.1, 5.0).thresholds(&configured, &CalibrationConfig::default());
+        assert!((thresholds.ear_enter - 0.21 * 1.25).abs() < 1e-9);
+        assert!((thresholds.ear_exit - 0.24 * 1.25).abs() < 1e-9);
+        assert!((thresholds.mar - 0.7).abs() < 1e-9);
+        assert_eq!((thresholds.yaw_center, thresholds.pitch_center), (0.1, 5.0));
+        assert_eq!((thresholds.yaw_enter, thresholds.yaw_exit, thresholds.pitch_enter, thresholds.pitch_exit), (0.5, 0.4, 30.0, 25.0));
+    }
+}
@@ -0,0 +1,136 @@
+// --- 다국어 알람 문구 카탈로그 ---
+// 알람 코드(예: "DROWSY")를 키로 언어별 알람 문구를 보관합니다.
+// 기본 문구(locales/*.json)는 바이너리에 포함되며, 디렉터리를 지정하면 그 안의 <언어>.json 파일로 문구를 덮어쓰거나 새 언어를 추가할 수 있습니다.
+use std::collections::HashMap; // 언어 → (알람 코드 → 문구) 매핑을 저장합니다.
+use std::fs; // 카탈로그 디렉터리의 파일을 읽기 위해 사용합니다.
+use std::path::Path; // 카탈로그 디렉터리 경로를 다루기 위해 사용합니다.
+
+// 사용자의 언어를 알 수 없거나, 해당 언어에 문구가 없을 때 사용하는 기본 언어입니다.
+pub const FALLBACK_LANGUAGE: &str = "ko";
+
+// 바이너리에 포함되는 기본 문구들입니다.
+const BUILTIN_CATALOGS: [(&str, &str); 3] = [
+    ("ko", include_str!("../locales/ko.json")),
+    ("en", include_str!("../locales/en.json")),
+    ("ja", include_str!("../locales/ja.json")),
+];
+
+// 언어별 알람 문구 카탈로그입니다.
+#[derive(Debug, Clone, Default)]
+pub struct AlarmCatalog {
+    languages: HashMap<String, HashMap<String, String>>,
+}
+
+impl AlarmCatalog {
+    // 기본 문구를 읽고, 디렉터리가 주어지면 그 안의 <언어>.json 파일로 덮어씁니다. 잘못된 파일은 로그만 남기고 건너뜁니다.
+    pub fn load(dir: Option<&Path>) -> Self {
+        let mut catalog = AlarmCatalog::default();
+        for (language, source) in BUILTIN_CATALOGS {
+            if let Err(e) = catalog.merge(language, source) { eprintln!("🔴 기본 알람 문구 파싱 실패 ({}): {}", language, e); }
+        }
+        let Some(dir) = dir else { return catalog };
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => { eprintln!("🔴 알람 문구 디렉터리를 읽을 수 없습니다 ({}): {}", dir.display(), e); return catalog; }
+        };
+        for path in entries.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") { continue; }
+            let Some(language) = path.file_stem().and_then(|stem| stem.to_str()).map(normalize_language) else { continue };
+            match fs::read_to_string(&path).map_err(|e| e.to_string()).and_then(|source| catalog.merge(&language, &source)) {
+                Ok(()) => log!("ℹ️ 알람 문구 로드: {} ({})", language, path.display()),
+                Err(e) => eprintln!("🔴 알람 문구 파일 로드 실패 ({}): {}", path.display(), e),
+            }
+        }
+        catalog
+    }
+
+    // 해당 언어의 문구가 있는지 확인합니다.
+    pub fn supports(&self, language: &str) -> bool { self.languages.contains_key(language) }
+
+    // 언어 코드(예: "en-US")를 카탈로그가 지원하는 언어(예: "en")로 바꿉니다. 지원하지 않으면 None을 반환합니다.
+    pub fn resolve(&self, language: &str) -> Option<String> {
+        let language = normalize_language(language);
+        self.supports(&language).then_some(language)
+    }
+
+    // Accept-Language 헤더(예: "en-US,en;q=0.9,ko;q=0.8")에서 지원하는 언어 중 가장 선호도가 높은 것을 고릅니다. (q=0은 원하지 않는 언어입니다.)
+    pub fn negotiate(&self, accept_language: &str) -> Option<String> {
+        let mut candidates: Vec<(String, f64)> = accept_language.split(',').filter_map(|part| {
+            let mut pieces = part.trim().split(';');
+            let language = pieces.next()?.trim();
+            let quality = pieces.find_map(|p| p.trim().strip_prefix("q=")).and_then(|q| q.parse().ok()).unwrap_or(1.0);
+            (quality > 0.0).then(|| (language.to_string(), quality))
+        }).collect();
+        candidates.sort_by(|a, b| b.1.total_cmp(&a.1)); // 선호도(q)가 높은 순으로 정렬 (같으면 헤더에 적힌 순서 유지)
+        candidates.iter().find_map(|(language, _)| self.resolve(language))
+    }
+
+    // 언어와 알람 코드에 맞는 문구를 만듭니다. 문구 안의 {이름} 자리는 args의 값으로 치환합니다.
+    // 해당 언어에 문구가 없으면 기본 언어(한국어)로, 그래도 없으면 알람 코드를 그대로 반환합니다.
+    pub fn message(&self, language: &str, code: &str, args: &[(&str, String)]) -> String {
+        let template = [language, FALLBACK_LANGUAGE].iter()
+            .find_map(|language| self.languages.get(*language).and_then(|messages| messages.get(code)))
+            .cloned()
+            .unwrap_or_else(|| code.to_string());
+        args.iter().fold(template, |text, (name, value)| text.replace(&format!("{{{}}}", name), value))
+    }
+
+    // JSON 문자열({"알람 코드": "문구"})을 읽어 해당 언어의 문구에 합칩니다.
+    fn merge(&mut self, language: &str, source: &str) -> Result<(), String> {
+        let messages: HashMap<String, String> = serde_json::from_str(source).map_err(|e| e.to_string())?;
+        self.languages.entry(language.to_string()).or_default().extend(messages);
+        Ok(())
+    }
+}
+
+// "en-US", "EN_us" 같은 언어 태그에서 주 언어 부분만 소문자로 뽑아냅니다. (예: "en")
+fn normalize_language(language: &str) -> String {
+    language.split(['-', '_']).next().unwrap_or("").trim().to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_normalizes_language_tags() {
+        let catalog = AlarmCatalog::load(None);
+        assert_eq!(catalog.resolve("en-US").as_deref(), Some("en"));
+        assert_eq!(catalog.resolve("JA_jp").as_deref(), Some("ja"));
+        assert_eq!(catalog.resolve("fr"), None);
+        assert_eq!(catalog.resolve(""), None);
+    }
+
+    #[test]
+    fn negotiate_picks_most_preferred_supported_language() {
+        let catalog = AlarmCatalog::load(None);
+        assert_eq!(catalog.negotiate("fr-FR,en;q=0.8,ko;q=0.9").as_deref(), Some("ko"));
+        assert_eq!(catalog.negotiate("ja, en").as_deref(), Some("ja")); // 선호도가 같으면 헤더에 적힌 순서를 따릅니다.
+        assert_eq!(catalog.negotiate("en;q=0, ja;q=0.1").as_deref(), Some("ja"));
+        assert_eq!(catalog.negotiate("en;q=0"), None);
+        assert_eq!(catalog.negotiate("fr, *;q=0.5"), None);
+    }
+
+    #[test]
+    fn message_falls_back_and_fills_arguments() {
+        let catalog = AlarmCatalog::load(None);
+        assert_eq!(catalog.message("en", "YAWN_REPEATED", &[("count", "3".to_string())]), "3 yawns detected! How about a quick stretch? 🤸");
+        assert_eq!(catalog.message("fr", "DROWSY", &[]), catalog.message(FALLBACK_LANGUAGE, "DROWSY", &[]));
+        assert_eq!(catalog.message("en", "NO_SUCH_ALARM", &[]), "NO_SUCH_ALARM");
+    }
+
+    #[test]
+    fn directory_overrides_and_adds_languages() {
+        let dir = std::env::temp_dir().join(format!("catalog-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("en.json"), r#"{"DROWSY": "Wake up!"}"#).unwrap();
+        fs::write(dir.join("fr-FR.json"), r#"{"DROWSY": "Réveillez-vous !"}"#).unwrap();
+        fs::write(dir.join("de.json"), "not json").unwrap();
+        let catalog = AlarmCatalog::load(Some(&dir));
+        assert_eq!(catalog.message("en", "DROWSY", &[]), "Wake up!");
+        assert_ne!(catalog.message("en", "DISTRACTED", &[]), "DISTRACTED"); // 덮어쓰지 않은 문구는 기본 문구를 유지합니다.
+        assert_eq!(catalog.resolve("fr").as_deref(), Some("fr"));
+        assert_eq!(catalog.resolve("de"), None);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
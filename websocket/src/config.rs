@@ -0,0 +1,507 @@
+// --- 서버 설정 ---
+// 리스너 주소, Redis 접속 정보, 채널 이름, 분석 임계값, 알람 정책 등 서버 동작 설정을 하나의 구조체로 모아둡니다.
+// 설정은 TOML 파일(CONFIG_PATH, 기본값 config.toml)에서 읽고, 일부 항목은 환경 변수로 덮어쓸 수 있습니다. (쿠버네티스 ConfigMap/Secret 호환)
+// 서버 시작 시 값의 범위와 상호 관계를 검증하여, 잘못된 설정으로는 서버가 뜨지 않도록 합니다.
+// SIGHUP을 받으면 설정을 다시 읽어, 분석 임계값/채널/알람 정책은 실행 중인 연결에도 바로 적용합니다. (리스너, Redis, 세션, 인증 설정은 재시작 필요)
+use crate::protocol::AlarmCode;
+use redis::aio::ConnectionManager; // Redis 설정으로 공유 연결을 만듭니다.
+use serde::Deserialize; // TOML 설정 파일을 Rust 구조체로 변환합니다.
+use std::env; // 환경 변수로 설정을 덮어쓰기 위해 사용합니다.
+use std::fs; // 설정 파일을 읽기 위해 사용합니다.
+use std::path::{Path, PathBuf}; // 설정 파일과 알람 문구 디렉터리 경로를 다루기 위해 사용합니다.
+use std::str::FromStr; // 환경 변수 문자열을 설정 값의 타입으로 변환하기 위해 사용합니다.
+use std::time::Duration; // 초 단위 설정을 엔진 설정의 Duration으로 바꾸기 위해 사용합니다.
+use websocket::engine::EngineConfig;
+use websocket::score::ScoreWeights;
+
+// 설정 파일 경로를 지정하지 않았을 때 찾는 기본 경로입니다. (없으면 기본값으로 실행)
+const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+// 서버 전체 설정입니다. 파일에 없는 항목은 기본값을 사용합니다.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub listener: ListenerConfig,
+    pub redis: RedisConfig,
+    pub channels: ChannelConfig,
+    pub delivery: DeliveryConfig,
+    pub session: SessionConfig,
+    pub auth: AuthConfig,
+    pub alarms: AlarmPolicy,
+    pub analysis: AnalysisConfig,
+}
+
+// 웹소켓 리스너 설정입니다.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ListenerConfig {
+    pub host: String,             // 바인딩할 주소 (0.0.0.0: 모든 네트워크 인터페이스)
+    pub port: u16,                // 바인딩할 포트 (환경 변수 WEBSOCKET_PORT)
+    pub ping_interval_secs: u64,  // 연결 유지를 위한 Ping 주기
+    pub shutdown_timeout_secs: u64, // 종료 신호를 받은 뒤 세션 정리와 남은 이벤트 발행을 합쳐 기다리는 최대 시간 (환경 변수 SHUTDOWN_TIMEOUT_SECS)
+}
+
+impl Default for ListenerConfig {
+    fn default() -> Self {
+        ListenerConfig { host: "0.0.0.0".to_string(), port: 9001, ping_interval_secs: 30, shutdown_timeout_secs: 20 }
+    }
+}
+
+// Redis 접속 설정입니다.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RedisConfig {
+    pub host: String, // 환경 변수 REDIS_HOST
+    pub port: u16,    // 환경 변수 REDIS_PORT
+    pub connect_retries: usize,   // 연결(재연결 포함)에 실패했을 때 다시 시도하는 횟수
+    pub connect_timeout_secs: u64, // 연결 시도 한 번의 제한 시간
+    pub response_timeout_secs: u64, // 명령 하나의 응답 제한 시간 (Redis가 멈춰도 세션 처리가 묶이지 않도록)
+}
+
+impl Default for RedisConfig {
+    fn default() -> Self {
+        RedisConfig { host: "127.0.0.1".to_string(), port: 6379, connect_retries: 6, connect_timeout_secs: 3, response_timeout_secs: 3 }
+    }
+}
+
+impl RedisConfig {
+    pub fn url(&self) -> String { format!("redis://{}:{}", self.host, self.port) }
+
+    // 모든 연결이 공유할 Redis 연결을 만듭니다. 연결이 끊기면 지수 백오프(100ms부터 2배씩)로 다시 연결합니다.
+    pub async fn connection_manager(&self, client: redis::Client) -> redis::RedisResult<ConnectionManager> {
+        let (connect_timeout, response_timeout) = (Duration::from_secs(self.connect_timeout_secs), Duration::from_secs(self.response_timeout_secs));
+        ConnectionManager::new_with_backoff_and_timeouts(client, 2, 100, self.connect_retries, response_timeout, connect_timeout).await
+    }
+}
+
+// Redis 채널과 키 이름 설정입니다.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChannelConfig {
+    pub meaningful_events: String,      // 상태 전환 등 의미 있는 이벤트를 발행하는 채널
+    pub raw_events: String,             // 일시정지 중의 원본 프레임 데이터를 발행하는 채널
+    pub calibration_key_prefix: String, // 사용자별 보정 결과를 저장하는 키의 접두사 (키: <접두사>:<userId>)
+}
+
+impl Default for ChannelConfig {
+    fn default() -> Self {
+        ChannelConfig {
+            meaningful_events: "attention-meaningful-events".to_string(),
+            raw_events: "attention-events".to_string(),
+            calibration_key_prefix: "attention-calibration".to_string(),
+        }
+    }
+}
+
+// 이벤트 발송 대기열과 출력 대상 설정입니다. 출력 대상에 보내지 못한 이벤트는 메모리에, 한도를 넘으면 디스크에 보관했다가 복구되면 순서대로 다시 보냅니다.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct DeliveryConfig {
+    pub memory_capacity: usize,   // 메모리에 보관하는 최대 이벤트 수 (넘으면 디스크로 옮깁니다.)
+    pub spill_dir: PathBuf,       // 디스크 보관 파일을 두는 디렉터리 (환경 변수 EVENT_SPILL_DIR)
+    pub spill_max_bytes: u64,     // 디스크에 보관하는 최대 크기 (넘는 이벤트는 버립니다.)
+    pub retry_initial_ms: u64,    // 발행 실패 후 첫 재시도까지의 대기 시간 (실패할수록 2배씩 늘어납니다.)
+    pub retry_max_ms: u64,        // 재시도 대기 시간의 상한
+    pub require_subscriber: bool, // true면 구독자가 하나도 없을 때(예: saver 재시작 중)도 발행 실패로 보고 다시 시도합니다. (pubsub 방식에만 해당)
+    pub output: OutputMode,       // 이벤트를 Redis에 쓰는 방식 (환경 변수 EVENT_OUTPUT_MODE)
+    pub stream_maxlen: usize,     // streams 방식에서 스트림마다 남겨두는 대략적인 최대 이벤트 수 (XADD MAXLEN ~)
+    pub sinks: Vec<SinkKind>,     // 이벤트를 보낼 출력 대상들 (환경 변수 EVENT_SINKS, 쉼표로 구분)
+    pub file_path: PathBuf,       // file 출력 대상이 이벤트를 덧붙이는 파일 (환경 변수 EVENT_FILE_PATH)
+    pub webhook_url: Option<String>, // webhook 출력 대상이 이벤트를 POST할 주소 (환경 변수 EVENT_WEBHOOK_URL)
+    pub webhook_timeout_secs: u64,   // 웹훅 요청 하나의 제한 시간
+}
+
+impl DeliveryConfig {
+    // Redis 연결이 필요한 출력 대상이 있는지 확인합니다. (없으면 Redis 없이 실행하며, 캘리브레이션도 저장하지 않습니다.)
+    pub fn uses_redis(&self) -> bool { self.sinks.contains(&SinkKind::Redis) }
+}
+
+// 이벤트 출력 대상의 종류입니다. (sinks 모듈)
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SinkKind { Redis, File, Stdout, Webhook }
+
+impl FromStr for SinkKind {
+    type Err = String;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "redis" => Ok(SinkKind::Redis),
+            "file" => Ok(SinkKind::File),
+            "stdout" => Ok(SinkKind::Stdout),
+            "webhook" => Ok(SinkKind::Webhook),
+            _ => Err(format!("unknown sink '{}'", value)),
+        }
+    }
+}
+
+// 이벤트를 Redis에 쓰는 방식입니다.
+// - pubsub: 채널에 PUBLISH합니다. 그 순간 구독 중인 소비자만 받습니다.
+// - streams: 채널 이름과 같은 스트림에 XADD합니다. 소비자는 컨슈머 그룹으로 읽고 처리한 이벤트를 확인(ACK)할 수 있습니다.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputMode { PubSub, Streams }
+
+impl FromStr for OutputMode {
+    type Err = String;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "pubsub" => Ok(OutputMode::PubSub),
+            "streams" => Ok(OutputMode::Streams),
+            _ => Err(format!("unknown output mode '{}'", value)),
+        }
+    }
+}
+
+impl Default for DeliveryConfig {
+    fn default() -> Self {
+        DeliveryConfig {
+            memory_capacity: 10_000,
+            spill_dir: PathBuf::from("spill"),
+            spill_max_bytes: 100 * 1024 * 1024,
+            retry_initial_ms: 100,
+            retry_max_ms: 10_000,
+            require_subscriber: true,
+            output: OutputMode::PubSub,
+            stream_maxlen: 100_000,
+            sinks: vec![SinkKind::Redis],
+            file_path: PathBuf::from("events.jsonl"),
+            webhook_url: None,
+            webhook_timeout_secs: 5,
+        }
+    }
+}
+
+// 세션 설정입니다.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SessionConfig {
+    pub resume_ttl_secs: u64, // 연결이 끊긴 세션을 재접속에 대비해 보관하는 시간 (환경 변수 SESSION_RESUME_TTL_SECS)
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        SessionConfig { resume_ttl_secs: 120 }
+    }
+}
+
+// 핸드셰이크 토큰 인증 설정입니다. 서명 키는 파일보다 환경 변수/Secret으로 넣는 것을 권장합니다.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    pub disabled: bool,                     // true면 인증 없이 연결을 받습니다. (로컬 개발용, 환경 변수 AUTH_DISABLED)
+    pub jwt_secret: Option<String>,         // HMAC 서명 키 (환경 변수 AUTH_JWT_SECRET)
+    pub jwt_secret_file: Option<PathBuf>,   // 서명 키를 담은 파일 (환경 변수 AUTH_JWT_SECRET_FILE)
+    pub issuer: Option<String>,             // 지정하면 토큰의 iss 클레임을 검증합니다. (환경 변수 AUTH_JWT_ISSUER)
+    pub audience: Option<String>,           // 지정하면 토큰의 aud 클레임을 검증합니다. (환경 변수 AUTH_JWT_AUDIENCE)
+}
+
+// 알람 정책입니다.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct AlarmPolicy {
+    pub catalog_dir: Option<PathBuf>, // 알람 문구를 덮어쓸 <언어>.json 파일들이 있는 디렉터리 (환경 변수 ALARM_CATALOG_DIR)
+    pub disabled: Vec<String>,        // 보내지 않을 알람 코드 목록 (예: ["YAWN_REPEATED"])
+    pub yawn_every: u32,              // 하품이 이 횟수의 배수가 될 때마다 알람을 보냅니다.
+    pub cooldown_secs: u64,           // 같은 종류의 알람을 다시 보내기까지 기다리는 최소 시간 (0이면 제한 없음)
+}
+
+impl Default for AlarmPolicy {
+    fn default() -> Self {
+        AlarmPolicy { catalog_dir: None, disabled: Vec::new(), yawn_every: 5, cooldown_secs: 0 }
+    }
+}
+
+impl AlarmPolicy {
+    // 해당 알람을 보내도록 설정되어 있는지 확인합니다.
+    pub fn enabled(&self, code: AlarmCode) -> bool { !self.disabled.iter().any(|disabled| disabled == code.key()) }
+}
+
+// 분석 엔진 설정입니다. 자주 조정하는 항목만 노출하고, 나머지는 엔진의 기본값을 사용합니다.
+// 임계값은 평균적인 사용자 기준이며, 보정(Calibration)이 끝난 세션에서는 사용자 기준값에 맞춰 옮겨서 적용합니다.
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(default, deny_unknown_fields)]
+pub struct AnalysisConfig {
+    pub ear_enter: f64,
+    pub ear_exit: f64,
+    pub mar: f64,
+    pub yaw_enter: f64,
+    pub yaw_exit: f64,
+    pub pitch_enter: f64,
+    pub pitch_exit: f64,
+    pub debounce_window: usize,        // 상태 전환 판정에 사용하는 최근 프레임 수(M)
+    pub debounce_votes: usize,         // 그중 새 상태를 가리켜야 하는 프레임 수(N)
+    pub enter_dwell_secs: f64,         // 이상 상태로 들어가기 위한 최소 유지 시간
+    pub exit_dwell_secs: f64,          // 집중 상태로 돌아오기 위한 최소 유지 시간
+    pub calibration_secs: f64,         // 보정 단계에서 기준값을 수집하는 시간
+    pub perclos_window_secs: f64,      // PERCLOS 계산 윈도우
+    pub perclos_enter: f64,            // 이 PERCLOS 이상이면 졸음
+    pub perclos_exit: f64,             // 졸음 상태에서는 이 PERCLOS 미만이어야 졸음 해제
+    pub blink_stats_interval_secs: f64, // BLINK_STATS 발행 주기
+    pub yawn_min_secs: f64,            // 하품으로 인정하는 최소 지속 시간
+    pub yawn_max_secs: f64,            // 하품으로 인정하는 최대 지속 시간
+    pub score_interval_secs: f64,      // ATTENTION_SCORE 발행 주기
+    pub score_weights: ScoreWeights,   // 종합 점수의 구성 요소별 가중치 (ear, mar, yaw, blink, state)
+    pub inactivity_timeout_secs: f64,  // 프레임이 이 시간 동안 오지 않으면 '비활성' (환경 변수 INACTIVITY_TIMEOUT_SECS)
+}
+
+impl Default for AnalysisConfig {
+    // 엔진의 기본 설정을 그대로 옮겨옵니다.
+    fn default() -> Self {
+        let engine = EngineConfig::default();
+        AnalysisConfig {
+            ear_enter: engine.thresholds.ear_enter,
+            ear_exit: engine.thresholds.ear_exit,
+            mar: engine.thresholds.mar,
+            yaw_enter: engine.thresholds.yaw_enter,
+            yaw_exit: engine.thresholds.yaw_exit,
+            pitch_enter: engine.thresholds.pitch_enter,
+            pitch_exit: engine.thresholds.pitch_exit,
+            debounce_window: engine.debounce.window_size,
+            debounce_votes: engine.debounce.min_votes,
+            enter_dwell_secs: engine.debounce.enter_dwell.as_secs_f64(),
+            exit_dwell_secs: engine.debounce.exit_dwell.as_secs_f64(),
+            calibration_secs: engine.calibration.duration.as_secs_f64(),
+            perclos_window_secs: engine.perclos.window.as_secs_f64(),
+            perclos_enter: engine.perclos.enter,
+            perclos_exit: engine.perclos.exit,
+            blink_stats_interval_secs: engine.blink.stats_interval.as_secs_f64(),
+            yawn_min_secs: engine.yawn.min_duration.as_secs_f64(),
+            yawn_max_secs: engine.yawn.max_duration.as_secs_f64(),
+            score_interval_secs: engine.score.interval.as_secs_f64(),
+            score_weights: engine.score.weights,
+            inactivity_timeout_secs: engine.inactivity.timeout.as_secs_f64(),
+        }
+    }
+}
+
+impl AnalysisConfig {
+    // 이 설정을 반영한 엔진 설정을 만듭니다.
+    pub fn engine_config(&self) -> EngineConfig {
+        let mut engine = EngineConfig::default();
+        engine.thresholds.ear_enter = self.ear_enter;
+        engine.thresholds.ear_exit = self.ear_exit;
+        engine.thresholds.mar = self.mar;
+        engine.thresholds.yaw_enter = self.yaw_enter;
+        engine.thresholds.yaw_exit = self.yaw_exit;
+        engine.thresholds.pitch_enter = self.pitch_enter;
+        engine.thresholds.pitch_exit = self.pitch_exit;
+        engine.debounce.window_size = self.debounce_window;
+        engine.debounce.min_votes = self.debounce_votes;
+        engine.debounce.enter_dwell = Duration::from_secs_f64(self.enter_dwell_secs);
+        engine.debounce.exit_dwell = Duration::from_secs_f64(self.exit_dwell_secs);
+        engine.calibration.duration = Duration::from_secs_f64(self.calibration_secs);
+        engine.perclos.window = Duration::from_secs_f64(self.perclos_window_secs);
+        engine.perclos.enter = self.perclos_enter;
+        engine.perclos.exit = self.perclos_exit;
+        engine.blink.stats_interval = Duration::from_secs_f64(self.blink_stats_interval_secs);
+        engine.yawn.min_duration = Duration::from_secs_f64(self.yawn_min_secs);
+        engine.yawn.max_duration = Duration::from_secs_f64(self.yawn_max_secs);
+        engine.score.interval = Duration::from_secs_f64(self.score_interval_secs);
+        engine.score.weights = self.score_weights;
+        engine.inactivity.timeout = Duration::from_secs_f64(self.inactivity_timeout_secs);
+        engine
+    }
+}
+
+impl ServerConfig {
+    // 설정 파일을 읽고 환경 변수로 덮어쓴 뒤 검증합니다.
+    // CONFIG_PATH로 지정한 파일이 없으면 에러이고, 기본 경로(config.toml)에 파일이 없으면 기본값을 사용합니다.
+    pub fn load() -> Result<Self, String> {
+        let mut config = match env::var("CONFIG_PATH") {
+            Ok(path) => ServerConfig::from_file(Path::new(&path))?,
+            Err(_) if Path::new(DEFAULT_CONFIG_PATH).exists() => ServerConfig::from_file(Path::new(DEFAULT_CONFIG_PATH))?,
+            Err(_) => ServerConfig::default(),
+        };
+        config.apply_env()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    // TOML 설정 파일을 읽습니다. (검증은 하지 않습니다.)
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let source = fs::read_to_string(path).map_err(|e| format!("설정 파일을 읽을 수 없습니다 ({}): {}", path.display(), e))?;
+        toml::from_str(&source).map_err(|e| format!("설정 파일 형식 오류 ({}): {}", path.display(), e))
+    }
+
+    // 환경 변수로 설정을 덮어씁니다. 기존 배포(쿠버네티스 ConfigMap 등)에서 쓰던 변수 이름을 그대로 받습니다.
+    fn apply_env(&mut self) -> Result<(), String> {
+        override_from_env(&mut self.listener.host, "WEBSOCKET_HOST")?;
+        override_from_env(&mut self.listener.port, "WEBSOCKET_PORT")?;
+        override_from_env(&mut self.listener.shutdown_timeout_secs, "SHUTDOWN_TIMEOUT_SECS")?;
+        override_from_env(&mut self.redis.host, "REDIS_HOST")?;
+        override_from_env(&mut self.redis.port, "REDIS_PORT")?;
+        override_from_env(&mut self.channels.meaningful_events, "MEANINGFUL_EVENTS_CHANNEL")?;
+        override_from_env(&mut self.channels.raw_events, "RAW_EVENTS_CHANNEL")?;
+        override_from_env(&mut self.delivery.spill_dir, "EVENT_SPILL_DIR")?;
+        override_from_env(&mut self.delivery.output, "EVENT_OUTPUT_MODE")?;
+        if let Ok(value) = env::var("EVENT_SINKS") {
+            self.delivery.sinks = value.split(',').map(|s| s.trim().parse()).collect::<Result<_, _>>()
+                .map_err(|_| format!("환경 변수 EVENT_SINKS의 값이 올바르지 않습니다: {}", value))?;
+        }
+        override_from_env(&mut self.delivery.file_path, "EVENT_FILE_PATH")?;
+        override_optional_from_env(&mut self.delivery.webhook_url, "EVENT_WEBHOOK_URL");
+        override_from_env(&mut self.session.resume_ttl_secs, "SESSION_RESUME_TTL_SECS")?;
+        override_from_env(&mut self.analysis.inactivity_timeout_secs, "INACTIVITY_TIMEOUT_SECS")?;
+        if let Ok(value) = env::var("AUTH_DISABLED") { self.auth.disabled = value == "true" || value == "1"; }
+        override_optional_from_env(&mut self.auth.jwt_secret, "AUTH_JWT_SECRET");
+        override_optional_from_env(&mut self.auth.jwt_secret_file, "AUTH_JWT_SECRET_FILE");
+        override_optional_from_env(&mut self.auth.issuer, "AUTH_JWT_ISSUER");
+        override_optional_from_env(&mut self.auth.audience, "AUTH_JWT_AUDIENCE");
+        override_optional_from_env(&mut self.alarms.catalog_dir, "ALARM_CATALOG_DIR");
+        Ok(())
+    }
+
+    // 설정 값의 범위와 상호 관계를 검사합니다. 문제가 있으면 모든 문제를 모아 하나의 메시지로 반환합니다.
+    pub fn validate(&self) -> Result<(), String> {
+        let mut errors = Vec::new();
+        let mut check = |ok: bool, message: &str| if !ok { errors.push(message.to_string()) };
+        let a = &self.analysis;
+        check(self.listener.port != 0, "listener.port는 0이 아니어야 합니다.");
+        check(self.listener.ping_interval_secs > 0, "listener.ping_interval_secs는 0보다 커야 합니다.");
+        check(0 < self.listener.shutdown_timeout_secs && self.listener.shutdown_timeout_secs <= MAX_DURATION_SECS as u64, "listener.shutdown_timeout_secs는 0보다 크고 86400(하루) 이하여야 합니다.");
+        check(!self.redis.host.is_empty(), "redis.host가 비어 있습니다.");
+        check(self.redis.connect_timeout_secs > 0 && self.redis.response_timeout_secs > 0, "redis의 제한 시간(*_timeout_secs)은 0보다 커야 합니다.");
+        check(!self.channels.meaningful_events.is_empty() && !self.channels.raw_events.is_empty(), "channels의 채널 이름이 비어 있습니다.");
+        check(!self.channels.calibration_key_prefix.is_empty(), "channels.calibration_key_prefix가 비어 있습니다.");
+        check(self.auth.disabled || self.auth.jwt_secret.as_deref().is_some_and(|s| !s.is_empty()) || self.auth.jwt_secret_file.is_some(),
+            "auth.jwt_secret 또는 auth.jwt_secret_file이 필요합니다. (로컬 개발에서 인증 없이 실행하려면 AUTH_DISABLED=true)");
+        check(self.delivery.output != OutputMode::Streams || self.delivery.stream_maxlen > 0, "delivery.stream_maxlen은 0보다 커야 합니다.");
+        let sinks = &self.delivery.sinks;
+        check(!sinks.is_empty(), "delivery.sinks에 출력 대상이 하나 이상 있어야 합니다.");
+        check(sinks.iter().enumerate().all(|(i, kind)| !sinks[..i].contains(kind)), "delivery.sinks에 같은 출력 대상이 두 번 이상 있습니다.");
+        check(!sinks.contains(&SinkKind::Webhook) || self.delivery.webhook_url.as_deref().is_some_and(|url| url.starts_with("http://") || url.starts_with("https://")),
+            "webhook 출력 대상에는 http:// 또는 https://로 시작하는 delivery.webhook_url이 필요합니다.");
+        check(self.delivery.webhook_timeout_secs > 0, "delivery.webhook_timeout_secs는 0보다 커야 합니다.");
+        check(self.delivery.memory_capacity > 0, "delivery.memory_capacity는 0보다 커야 합니다.");
+        check(0 < self.delivery.retry_initial_ms && self.delivery.retry_initial_ms <= self.delivery.retry_max_ms, "delivery.retry_initial_ms는 0보다 크고 retry_max_ms 이하여야 합니다.");
+        check(self.alarms.yawn_every > 0, "alarms.yawn_every는 0보다 커야 합니다.");
+        check(self.alarms.cooldown_secs <= MAX_DURATION_SECS as u64, "alarms.cooldown_secs는 86400(하루) 이하여야 합니다.");
+        for code in &self.alarms.disabled {
+            check(AlarmCode::ALL.iter().any(|c| c.key() == code), &format!("alarms.disabled에 알 수 없는 알람 코드가 있습니다: {}", code));
+        }
+        check(0.0 < a.ear_enter && a.ear_enter < a.ear_exit, "analysis.ear_enter는 0보다 크고 ear_exit보다 작아야 합니다.");
+        check(a.mar > 0.0, "analysis.mar은 0보다 커야 합니다.");
+        check(0.0 < a.yaw_exit && a.yaw_exit <= a.yaw_enter, "analysis.yaw_exit는 0보다 크고 yaw_enter 이하여야 합니다.");
+        check(0.0 < a.pitch_exit && a.pitch_exit <= a.pitch_enter, "analysis.pitch_exit는 0보다 크고 pitch_enter 이하여야 합니다.");
+        check(0 < a.debounce_votes && a.debounce_votes <= a.debounce_window, "analysis.debounce_votes는 1 이상, debounce_window 이하여야 합니다.");
+        check(0.0 <= a.perclos_exit && a.perclos_exit <= a.perclos_enter && a.perclos_enter <= 1.0, "analysis.perclos_exit ≤ perclos_enter ≤ 1 이어야 합니다.");
+        check(0.0 < a.yawn_min_secs && a.yawn_min_secs < a.yawn_max_secs, "analysis.yawn_min_secs는 0보다 크고 yawn_max_secs보다 작아야 합니다.");
+        let w = &a.score_weights;
+        let weights = [w.ear, w.mar, w.yaw, w.blink, w.state];
+        check(weights.iter().all(|w| w.is_finite() && *w >= 0.0) && weights.iter().sum::<f64>() > 0.0, "analysis.score_weights는 모두 0 이상이고 합이 0보다 커야 합니다.");
+        let durations = [a.enter_dwell_secs, a.exit_dwell_secs, a.calibration_secs, a.perclos_window_secs, a.blink_stats_interval_secs, a.yawn_min_secs, a.yawn_max_secs, a.score_interval_secs, a.inactivity_timeout_secs];
+        check(durations.iter().all(|d| *d > 0.0 && *d <= MAX_DURATION_SECS), "analysis의 시간 설정(*_secs)은 모두 0보다 크고 86400(하루) 이하여야 합니다.");
+        if errors.is_empty() { Ok(()) } else { Err(errors.join("\n")) }
+    }
+
+    // 실행 중에는 바꿀 수 없어 서버를 다시 시작해야 적용되는 항목 중, 다른 설정과 값이 다른 항목의 이름을 돌려줍니다. (설정 리로드)
+    pub fn restart_required(&self, other: &ServerConfig) -> Vec<&'static str> {
+        let changed = [
+            ("listener", self.listen_addr() != other.listen_addr()),
+            ("redis", self.redis != other.redis),
+            ("session", self.session != other.session),
+            ("auth", self.auth != other.auth),
+            ("delivery", self.delivery != other.delivery),
+        ];
+        changed.into_iter().filter(|(_, changed)| *changed).map(|(name, _)| name).collect()
+    }
+
+    // 바인딩할 리스너 주소입니다. (예: "0.0.0.0:9001")
+    pub fn listen_addr(&self) -> String { format!("{}:{}", self.listener.host, self.listener.port) }
+}
+
+// 시간 설정(*_secs)의 상한입니다. (Duration 변환과 밀리초 계산이 넘치지 않도록)
+const MAX_DURATION_SECS: f64 = 86_400.0;
+
+// 환경 변수가 있으면 설정 값을 덮어씁니다. 값의 형식이 잘못되었으면 에러를 반환합니다.
+fn override_from_env<T: FromStr>(field: &mut T, name: &str) -> Result<(), String> {
+    if let Ok(value) = env::var(name) {
+        *field = value.parse().map_err(|_| format!("환경 변수 {}의 값이 올바르지 않습니다: {}", name, value))?;
+    }
+    Ok(())
+}
+
+// 환경 변수가 있으면 선택 설정 값을 채웁니다.
+fn override_optional_from_env<T: From<String>>(field: &mut Option<T>, name: &str) {
+    if let Ok(value) = env::var(name) { *field = Some(T::from(value)); }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 인증 키만 넣은 기본 설정입니다. (기본값은 인증을 켠 채로 시작하므로 키가 필요합니다.)
+    fn valid() -> ServerConfig {
+        let mut config = ServerConfig::default();
+        config.auth.jwt_secret = Some("secret".to_string());
+        config
+    }
+
+    fn errors(config: &ServerConfig) -> Vec<String> {
+        config.validate().err().map(|e| e.lines().map(str::to_string).collect()).unwrap_or_default()
+    }
+
+    #[test]
+    fn default_requires_auth_secret() {
+        assert!(valid().validate().is_ok());
+        let errors = errors(&ServerConfig::default());
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("auth.jwt_secret"));
+        let mut config = ServerConfig::default();
+        config.auth.disabled = true;
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn repository_config_file_is_valid() {
+        let mut config = ServerConfig::from_file(&Path::new(env!("CARGO_MANIFEST_DIR")).join(DEFAULT_CONFIG_PATH)).unwrap();
+        assert!(!config.auth.disabled);
+        config.auth.jwt_secret = Some("secret".to_string());
+        assert_eq!(config.validate(), Ok(()));
+    }
+
+    #[test]
+    fn validate_reports_every_problem() {
+        let mut config = valid();
+        config.listener.port = 0;
+        config.analysis.ear_enter = 0.3; // ear_exit(0.24)보다 큽니다.
+        config.delivery.sinks = vec![SinkKind::File, SinkKind::Webhook, SinkKind::File];
+        config.alarms.disabled = vec!["NO_SUCH_ALARM".to_string()];
+        let errors = errors(&config);
+        for prefix in ["listener.port", "analysis.ear_enter", "delivery.sinks에 같은", "webhook 출력 대상", "alarms.disabled"] {
+            assert!(errors.iter().any(|e| e.starts_with(prefix)), "missing '{}' in {:?}", prefix, errors);
+        }
+        assert_eq!(errors.len(), 5);
+    }
+
+    #[test]
+    fn sink_kinds_and_output_modes_parse() {
+        assert_eq!("stdout".parse::<SinkKind>(), Ok(SinkKind::Stdout));
+        assert!("kafka".parse::<SinkKind>().is_err());
+        assert_eq!("streams".parse::<OutputMode>(), Ok(OutputMode::Streams));
+    }
+
+    // 환경 변수는 프로세스 전체에서 공유되므로, 환경 변수를 바꾸는 검사는 이 테스트 하나에서만 합니다.
+    #[test]
+    fn environment_overrides_file_values() {
+        let vars = ["WEBSOCKET_PORT", "EVENT_SINKS", "AUTH_DISABLED", "ALARM_CATALOG_DIR"];
+        env::set_var("WEBSOCKET_PORT", "9100");
+        env::set_var("EVENT_SINKS", "stdout, file");
+        env::set_var("AUTH_DISABLED", "1");
+        env::set_var("ALARM_CATALOG_DIR", "/etc/alarms");
+        let mut config = ServerConfig::default();
+        config.apply_env().unwrap();
+        assert_eq!(config.listener.port, 9100);
+        assert_eq!(config.delivery.sinks, vec![SinkKind::Stdout, SinkKind::File]);
+        assert!(config.auth.disabled);
+        assert_eq!(config.alarms.catalog_dir.as_deref(), Some(Path::new("/etc/alarms")));
+
+        // 형식이 잘못된 값은 무시하지 않고 에러로 알려줍니다.
+        env::set_var("WEBSOCKET_PORT", "not-a-port");
+        assert!(ServerConfig::default().apply_env().unwrap_err().contains("WEBSOCKET_PORT"));
+        env::set_var("WEBSOCKET_PORT", "9100");
+        env::set_var("EVENT_SINKS", "stdout,kafka");
+        assert!(ServerConfig::default().apply_env().unwrap_err().contains("EVENT_SINKS"));
+        for var in vars { env::remove_var(var); }
+    }
+}
@@ -0,0 +1,706 @@
+// --- 집중도 분석 엔진 ---
+// 랜드마크 프레임(타임스탬프 포함)을 입력받아 상태 전환과 감지된 이벤트를 돌려주는 순수 분석 로직입니다.
+// 소켓이나 Redis 같은 I/O에 전혀 의존하지 않으므로, 배치 재분석 도구나 단위 테스트에서 그대로 재사용할 수 있습니다.
+use crate::calibration::{Calibration, CalibrationCollector, CalibrationConfig};
+use crate::eyes::{BlinkConfig, EyeClosureTracker, EyeMetrics, PerclosConfig};
+use crate::features::{FrameFeatures, HeadPose, Landmark};
+use crate::score::{ScoreAccumulator, ScoreComponents, ScoreConfig};
+use crate::yawn::{YawnConfig, YawnDetector};
+use serde::Serialize; // 현재 적용 중인 임계값을 이벤트 payload에 담기 위해 사용합니다.
+use serde_json::{json, Value}; // 이벤트 payload를 JSON 값으로 만들기 위해 사용합니다.
+use std::collections::VecDeque; // 최근 프레임 판정 결과를 일정 개수만큼 보관하기 위한 큐입니다.
+use std::time::Duration; // 디바운스 유지 시간 설정에 사용합니다.
+
+// 클라이언트의 집중도 상태를 명확하게 관리하기 위한 '상태 머신(State Machine)'입니다.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum AttentionState {
+    Focused,      // 집중 상태
+    Drowsy,       // 졸음 상태
+    Distracted(DistractionKind), // 주의 분산 상태 (분산 유형 포함)
+    UserLeft,     // 자리 비움 상태
+    Inactive,     // 프레임이 끊긴 상태 (브라우저 탭이 백그라운드로 가는 등)
+    Paused,       // 사용자가 직접 일시정지한 상태
+}
+
+// 주의 분산의 세부 유형입니다.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum DistractionKind {
+    LookingAway, // 고개를 좌우로 돌려 화면 밖을 봄
+    LookingDown, // 고개를 숙여 휴대폰이나 노트 등을 봄
+}
+
+impl DistractionKind {
+    // 이벤트 payload에 기록할 분산 유형 이름입니다.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DistractionKind::LookingAway => "LOOKING_AWAY",
+            DistractionKind::LookingDown => "LOOKING_DOWN",
+        }
+    }
+}
+
+impl AttentionState {
+    // 이벤트 payload에 기록할 상태 이름입니다.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AttentionState::Focused => "FOCUSED",
+            AttentionState::Drowsy => "DROWSY",
+            AttentionState::Distracted(_) => "DISTRACTED",
+            AttentionState::UserLeft => "USER_LEFT",
+            AttentionState::Inactive => "INACTIVE",
+            AttentionState::Paused => "PAUSED",
+        }
+    }
+
+    // 이 상태가 끝났을 때 발행하는 종료 이벤트 타입입니다. (예: 졸음 상태 종료 → "DROWSINESS_ENDED")
+    pub fn ended_event_type(&self) -> &'static str {
+        match self {
+            AttentionState::Focused => "FOCUS_ENDED",
+            AttentionState::Drowsy => "DROWSINESS_ENDED",
+            AttentionState::Distracted(_) => "DISTRACTION_ENDED",
+            AttentionState::UserLeft => "USER_LEFT_ENDED",
+            AttentionState::Inactive => "INACTIVITY_ENDED",
+            AttentionState::Paused => "PAUSE_ENDED",
+        }
+    }
+}
+
+// 이전 상태와 새 상태를 기반으로 "DROWSINESS_STARTED" 등 의미 있는 이벤트 타입을 결정합니다.
+pub fn transition_event_type(from: AttentionState, to: AttentionState) -> &'static str {
+    match (from, to) {
+        (AttentionState::Paused, AttentionState::Focused) => "SESSION_RESUMED",
+        (_, AttentionState::Focused) => "FOCUS_RESTORED",
+        (_, AttentionState::Paused) => "SESSION_PAUSED",
+        (_, AttentionState::Drowsy) => "DROWSINESS_STARTED",
+        (_, AttentionState::Distracted(_)) => "DISTRACTION_STARTED",
+        (_, AttentionState::UserLeft) => "USER_LEFT",
+        (_, AttentionState::Inactive) => "INACTIVITY_STARTED",
+    }
+}
+
+// 분석에 사용할 각종 임계값(Threshold)입니다. 진입(enter)과 해제(exit) 임계값을 따로 두어 히스테리시스를 적용합니다.
+// 보정(Calibration)을 마치면 사용자의 기준값(눈/입 기준값, 정면 방향)에 맞춰 옮겨서 적용합니다.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Thresholds {
+    pub ear_enter: f64,  // 두 눈의 EAR이 모두 이 값보다 작으면 '눈을 감았다'고 판단합니다.
+    pub ear_exit: f64,   // 눈을 감은 상태에서는 EAR이 이 값 이상으로 회복되어야 '눈을 떴다'고 판단합니다.
+    pub mar: f64,        // 이 값보다 MAR이 크면 '하품'으로 판단합니다.
+    pub yaw_center: f64, // 사용자가 화면을 정면으로 볼 때의 고개 회전 값 (카메라 각도 보정용)
+    pub yaw_enter: f64,  // 기준 방향과의 고개 회전 차이가 이 값보다 크면 '주의 분산'으로 판단합니다.
+    pub yaw_exit: f64,   // 주의 분산 상태에서는 기준 방향과의 차이가 이 값 이하로 돌아와야 분산에서 벗어난 것으로 판단합니다.
+    pub pitch_center: f64, // 사용자가 화면을 정면으로 볼 때의 Pitch 각도(도)
+    pub pitch_enter: f64,  // 기준 각도보다 이 각도(도) 이상 고개를 숙이면 '고개 숙임' 주의 분산으로 판단합니다.
+    pub pitch_exit: f64,   // 고개 숙임 상태에서는 기준 각도와의 차이가 이 각도(도) 이하로 돌아와야 분산에서 벗어난 것으로 판단합니다.
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Thresholds { ear_enter: 0.21, ear_exit: 0.24, mar: 0.6, yaw_center: 0.0, yaw_enter: 0.3, yaw_exit: 0.25, pitch_center: 0.0, pitch_enter: 20.0, pitch_exit: 15.0 }
+    }
+}
+
+// 상태 전환을 확정하기 전에 얼마나 오래, 얼마나 자주 조건이 유지되어야 하는지 정의하는 설정입니다.
+// min_votes/window_size는 'N-of-M 프레임' 조건이고, enter_dwell/exit_dwell은 최소 유지 시간 조건입니다. (둘 다 만족해야 전환)
+#[derive(Debug, Clone, Copy)]
+pub struct DebounceConfig {
+    pub window_size: usize,     // 최근 몇 개의 프레임(M)을 살펴볼지
+    pub min_votes: usize,       // 그중 몇 개(N) 이상이 새 상태를 가리켜야 하는지
+    pub enter_dwell: Duration,  // Focused → 이상 상태로 들어갈 때 필요한 최소 유지 시간
+    pub exit_dwell: Duration,   // 이상 상태 → Focused로 돌아올 때 필요한 최소 유지 시간
+}
+
+impl Default for DebounceConfig {
+    fn default() -> Self {
+        // 클라이언트는 약 1초에 한 프레임을 보내므로, 최근 4프레임 중 3프레임 + 2초 유지를 기본값으로 합니다.
+        DebounceConfig { window_size: 4, min_votes: 3, enter_dwell: Duration::from_secs(2), exit_dwell: Duration::from_secs(2) }
+    }
+}
+
+// 클라이언트가 프레임을 보내지 않는 상태를 감지하는 설정입니다.
+#[derive(Debug, Clone, Copy)]
+pub struct InactivityConfig {
+    pub timeout: Duration, // 이 시간 동안 프레임(또는 얼굴 미감지 신호)이 오지 않으면 '비활성' 상태로 전환합니다.
+}
+
+impl Default for InactivityConfig {
+    fn default() -> Self {
+        InactivityConfig { timeout: Duration::from_secs(15) }
+    }
+}
+
+// 엔진 전체 설정입니다.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EngineConfig {
+    pub thresholds: Thresholds,           // 보정 전에 사용하는 기본 임계값
+    pub debounce: DebounceConfig,
+    pub calibration: CalibrationConfig,
+    pub perclos: PerclosConfig,
+    pub blink: BlinkConfig,
+    pub yawn: YawnConfig,
+    pub score: ScoreConfig,
+    pub inactivity: InactivityConfig,
+}
+
+// 클라이언트가 'status_update'로 알려오는 상태 신호입니다.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClientStatus {
+    NoFaceDetected, // 얼굴이 감지되지 않음
+    Paused,         // 사용자가 일시정지함
+    Resumed,        // 사용자가 일시정지를 해제함
+}
+
+impl ClientStatus {
+    // 클라이언트가 보내는 상태 문자열을 해석합니다. 알 수 없는 값이면 None을 반환합니다.
+    pub fn parse(status: &str) -> Option<Self> {
+        match status {
+            "no_face_detected" => Some(ClientStatus::NoFaceDetected),
+            "paused" => Some(ClientStatus::Paused),
+            "resumed" => Some(ClientStatus::Resumed),
+            _ => None,
+        }
+    }
+}
+
+// 엔진이 프레임을 처리한 결과로 돌려주는 이벤트입니다. 서버는 이것을 Redis 이벤트와 알람으로 변환합니다.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EngineEvent {
+    StateEnded { state: AttentionState, duration_ms: u64 },      // 방금 끝난 상태와 그 지속 시간
+    StateChanged { from: AttentionState, to: AttentionState, eyes: EyeMetrics, pose: Option<HeadPose> }, // 확정된 상태 전환 (전환 시점의 눈 지표와 머리 자세 포함)
+    YawnDetected { count: u32, started_ms: u64, duration_ms: u64, peak_mar: f64 }, // 하품 한 번 (세션 누적 횟수, 시작 시각, 지속 시간, 최대 MAR)
+    BlinkStats { eyes: EyeMetrics },                             // 주기적인 깜빡임 통계 (분당 깜빡임 횟수, 평균 깜빡임 시간 등)
+    AttentionScore { score: f64, components: ScoreComponents },  // 주기적인 종합 집중도 점수 (0 ~ 100)
+    CalibrationStarted,                                          // 보정 단계 시작
+    CalibrationCompleted { calibration: Calibration, thresholds: Thresholds }, // 보정 완료 (기준값과 새 임계값)
+}
+
+impl EngineEvent {
+    // Redis에 발행할 이벤트 타입 이름입니다.
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            EngineEvent::StateEnded { state, .. } => state.ended_event_type(),
+            EngineEvent::StateChanged { from, to, .. } => transition_event_type(*from, *to),
+            EngineEvent::YawnDetected { .. } => "YAWN_DETECTED",
+            EngineEvent::BlinkStats { .. } => "BLINK_STATS",
+            EngineEvent::AttentionScore { .. } => "ATTENTION_SCORE",
+            EngineEvent::CalibrationStarted => "CALIBRATION_STARTED",
+            EngineEvent::CalibrationCompleted { .. } => "CALIBRATION_COMPLETED",
+        }
+    }
+
+    // Redis에 발행할 이벤트 payload입니다.
+    pub fn payload(&self) -> Value {
+        match self {
+            EngineEvent::StateEnded { state, duration_ms } => json!({ "state": state.as_str(), "durationMs": duration_ms }),
+            EngineEvent::StateChanged { to: AttentionState::Distracted(kind), eyes, pose, .. } => json!({ "state": "DISTRACTED", "distraction": kind.as_str(), "eyes": eyes, "headPose": pose }),
+            EngineEvent::StateChanged { to, eyes, pose, .. } => json!({ "state": to.as_str(), "eyes": eyes, "headPose": pose }),
+            EngineEvent::YawnDetected { count, started_ms, duration_ms, peak_mar } => json!({ "count": count, "startedAtMs": started_ms, "durationMs": duration_ms, "peakMar": peak_mar }),
+            EngineEvent::BlinkStats { eyes } => json!(eyes),
+            EngineEvent::AttentionScore { score, components } => json!({ "score": score, "components": components }),
+            EngineEvent::CalibrationStarted => json!({}),
+            EngineEvent::CalibrationCompleted { calibration, thresholds } => json!({ "calibration": calibration, "thresholds": thresholds }),
+        }
+    }
+}
+
+// 프레임 단위의 '원시(raw)' 상태 판정을 받아, 히스테리시스를 적용한 뒤 확정된 상태 전환만 돌려주는 디바운서입니다.
+// 눈 한 번 깜빡임처럼 한 프레임짜리 변화로 상태가 뒤집히고 알람이 울리는 것을 막습니다.
+struct StateDebouncer {
+    config: DebounceConfig,
+    committed: AttentionState,                    // 현재 확정된 상태
+    recent: VecDeque<AttentionState>,             // 최근 M개 프레임의 원시 판정 결과
+    candidate: Option<(AttentionState, u64)>,     // 전환 후보 상태와, 그 후보가 처음 관측된 시각(ms)
+}
+
+impl StateDebouncer {
+    fn new(config: DebounceConfig, initial: AttentionState) -> Self {
+        StateDebouncer { config, committed: initial, recent: VecDeque::with_capacity(config.window_size), candidate: None }
+    }
+
+    // 새 프레임의 원시 판정을 반영합니다. 전환이 확정되면 (새 상태, 그 상태가 실제로 시작된 시각)을 반환합니다.
+    fn observe(&mut self, raw: AttentionState, now_ms: u64) -> Option<(AttentionState, u64)> {
+        if self.recent.len() == self.config.window_size { self.recent.pop_front(); }
+        self.recent.push_back(raw);
+
+        // 확정 상태와 다른 판정이 들어오면, 그 상태를 새 후보로 삼습니다. (후보가 바뀌면 시작 시각도 새로 기록)
+        if raw != self.committed && self.candidate.map(|(state, _)| state) != Some(raw) {
+            self.candidate = Some((raw, now_ms));
+        }
+
+        let (candidate, since_ms) = self.candidate?;
+        let votes = self.recent.iter().filter(|&&s| s == candidate).count();
+        if votes == 0 { self.candidate = None; return None; } // 후보가 창(window)에서 완전히 사라지면 후보를 폐기합니다.
+
+        // Focused로 돌아오는 '해제' 전환과, 이상 상태로 들어가는 '진입' 전환은 서로 다른 유지 시간을 요구합니다.
+        let dwell = if candidate == AttentionState::Focused { self.config.exit_dwell } else { self.config.enter_dwell };
+        if votes >= self.config.min_votes && Duration::from_millis(now_ms.saturating_sub(since_ms)) >= dwell {
+            self.force(candidate);
+            return Some((candidate, since_ms));
+        }
+        None
+    }
+
+    // 일시정지/재개처럼 사용자가 명시적으로 요청한 전환은 디바운스 없이 즉시 확정합니다.
+    fn force(&mut self, state: AttentionState) {
+        self.committed = state;
+        self.recent.clear();
+        self.candidate = None;
+    }
+
+    // 설정을 바꿉니다. 창(window)이 줄어들면 오래된 판정부터 버립니다.
+    fn set_config(&mut self, config: DebounceConfig) {
+        self.config = config;
+        while self.recent.len() > config.window_size { self.recent.pop_front(); }
+    }
+}
+
+// 한 세션의 집중도 분석 상태를 모두 담고 있는 엔진입니다. 모든 시각은 밀리초 단위 타임스탬프(ms)로 받습니다.
+pub struct AttentionEngine {
+    config: EngineConfig,
+    thresholds: Thresholds,                       // 현재 적용 중인 임계값 (보정 후에는 사용자 기준값에 상대적인 값)
+    calibration: Option<Calibration>,             // 적용된 보정 결과
+    collector: Option<CalibrationCollector>,      // 보정 단계가 진행 중이면 기준값 수집기
+    debouncer: StateDebouncer,
+    eyes: EyeClosureTracker,                      // PERCLOS 등 눈 감김 지표 추적기
+    blink_stats_at_ms: u64,                       // 마지막으로 BLINK_STATS를 발행한 시각
+    score: ScoreAccumulator,                      // 종합 집중도 점수 누적기
+    last_pose: Option<HeadPose>,                  // 가장 최근 프레임의 머리 자세
+    last_frame_ms: u64,                           // 마지막으로 프레임(또는 얼굴 미감지 신호)을 받은 시각
+    state: AttentionState,   // 현재 확정된 상태
+    state_since_ms: u64,     // 현재 상태가 시작된 시각
+    suspended: bool,         // 연결이 끊겨 현재 상태를 정산해 둔 동안 true (재접속하면 현재 상태를 다시 엽니다.)
+    yawn: YawnDetector,      // 하품 구간 감지기
+    yawn_count: u32,         // 하품 횟수를 세기 위한 카운터
+}
+
+impl AttentionEngine {
+    // 세션 시작 시각(ms)을 기준으로 'Focused' 상태에서 시작하는 엔진을 만듭니다.
+    pub fn new(config: EngineConfig, start_ms: u64) -> Self {
+        let state = AttentionState::Focused;
+        AttentionEngine {
+            config,
+            thresholds: config.thresholds,
+            calibration: None,
+            collector: None,
+            debouncer: StateDebouncer::new(config.debounce, state),
+            eyes: EyeClosureTracker::new(config.perclos, config.blink),
+            blink_stats_at_ms: start_ms,
+            score: ScoreAccumulator::new(config.score, start_ms),
+            last_pose: None,
+            last_frame_ms: start_ms,
+            state,
+            state_since_ms: start_ms,
+            suspended: false,
+            yawn: YawnDetector::new(config.yawn),
+            yawn_count: 0,
+        }
+    }
+
+    // 진행 중인 세션의 상태와 누적 기록은 유지한 채 엔진 설정을 바꿉니다. (설정 리로드)
+    // 보정이 끝난 세션은 새 임계값을 사용자 기준값에 맞춰 옮기고, 보정 전이면 새 임계값을 그대로 사용합니다.
+    pub fn reconfigure(&mut self, config: EngineConfig) {
+        self.config = config;
+        self.thresholds = match self.calibration { Some(calibration) => calibration.thresholds(&config.thresholds, &config.calibration), None => config.thresholds };
+        self.debouncer.set_config(config.debounce);
+        self.eyes.set_config(config.perclos, config.blink);
+        self.score.set_config(config.score);
+        self.yawn.set_config(config.yawn);
+    }
+
+    // 현재 적용 중인 임계값을 반환합니다.
+    pub fn thresholds(&self) -> Thresholds { self.thresholds }
+
+    // 적용된 보정 결과를 반환합니다. (보정 전이면 None)
+    pub fn calibration(&self) -> Option<Calibration> { self.calibration }
+
+    // 보정 단계가 진행 중인지 여부를 반환합니다.
+    pub fn is_calibrating(&self) -> bool { self.collector.is_some() }
+
+    // 보정 단계를 (다시) 시작합니다. 보정이 끝날 때까지의 프레임은 기준값 수집에만 사용되고 상태 판정에는 쓰이지 않습니다.
+    pub fn start_calibration(&mut self, timestamp_ms: u64) -> Vec<EngineEvent> {
+        self.collector = Some(CalibrationCollector::new(timestamp_ms));
+        vec![EngineEvent::CalibrationStarted]
+    }
+
+    // 이전 세션에서 저장해 둔 보정 결과를 적용합니다. 진행 중이던 보정 단계는 취소됩니다.
+    pub fn apply_calibration(&mut self, calibration: Calibration) {
+        self.collector = None;
+        self.calibration = Some(calibration);
+        self.thresholds = calibration.thresholds(&self.config.thresholds, &self.config.calibration);
+    }
+
+    // 현재 확정된 상태를 반환합니다.
+    pub fn state(&self) -> AttentionState { self.state }
+
+    // 현재 상태가 시작된 시각(ms)을 반환합니다.
+    pub fn state_since_ms(&self) -> u64 { self.state_since_ms }
+
+    // 랜드마크 프레임 하나를 분석하여, 이번 프레임으로 인해 발생한 이벤트들을 반환합니다.
+    pub fn process_frame(&mut self, landmarks: &[Landmark], timestamp_ms: u64) -> Vec<EngineEvent> {
+        // 일시정지 중에는 분석을 건너뜁니다.
+        if self.state == AttentionState::Paused { return Vec::new(); }
+
+        let features = FrameFeatures::from_landmarks(landmarks);
+        let mut events = Vec::new();
+        self.wake(timestamp_ms, &mut events);
+
+        // 보정 단계 중에는 기준값만 수집하고, 수집이 끝나면 새 임계값을 적용합니다.
+        if let Some(collector) = self.collector.as_mut() {
+            collector.add(&features);
+            if let Some(calibration) = collector.finish(timestamp_ms, &self.config.calibration) {
+                self.apply_calibration(calibration);
+                events.push(EngineEvent::CalibrationCompleted { calibration, thresholds: self.thresholds });
+            }
+            return events;
+        }
+
+        let thresholds = self.thresholds;
+        self.eyes.update(features.ear_left, features.ear_right, &thresholds, timestamp_ms);
+
+        // 정해진 주기마다 깜빡임 통계를 남깁니다. 프레임이 드물어 깜빡임을 잴 수 없는 동안에는 빈 통계를 보내지 않습니다.
+        if timestamp_ms.saturating_sub(self.blink_stats_at_ms) >= self.config.blink.stats_interval.as_millis() as u64 {
+            self.blink_stats_at_ms = timestamp_ms;
+            let eyes = self.eyes.metrics(timestamp_ms);
+            if eyes.blink_rate_per_min.is_some() { events.push(EngineEvent::BlinkStats { eyes }); }
+        }
+
+        // 현재 상태에 따라 진입/해제 임계값을 다르게 적용합니다. (히스테리시스)
+        let yaw_threshold = if self.state == AttentionState::Distracted(DistractionKind::LookingAway) { thresholds.yaw_exit } else { thresholds.yaw_enter };
+        let pitch_threshold = if self.state == AttentionState::Distracted(DistractionKind::LookingDown) { thresholds.pitch_exit } else { thresholds.pitch_enter };
+        self.last_pose = features.pose;
+        let looking_down = features.pose.is_some_and(|pose| pose.pitch - thresholds.pitch_center > pitch_threshold);
+
+        // 졸음은 한 프레임의 EAR이 아니라 최근 윈도우의 PERCLOS로 판정하고, 나머지는 이번 프레임 값으로 원시 상태를 판정합니다.
+        // 고개를 숙이면 눈이 감긴 것처럼 보이므로, 고개 숙임을 졸음보다 먼저 판정합니다.
+        let raw_state = if looking_down {
+            AttentionState::Distracted(DistractionKind::LookingDown)
+        } else if self.eyes.is_drowsy(self.state == AttentionState::Drowsy) {
+            AttentionState::Drowsy
+        } else if (features.head_yaw - thresholds.yaw_center).abs() > yaw_threshold {
+            AttentionState::Distracted(DistractionKind::LookingAway)
+        } else {
+            AttentionState::Focused
+        };
+
+        // 하품 구간이 끝나 하품으로 인정되면, 누적 횟수와 함께 이벤트를 한 번만 남깁니다.
+        if let Some(episode) = self.yawn.update(features.mar, thresholds.mar, timestamp_ms) {
+            self.yawn_count += 1;
+            events.push(EngineEvent::YawnDetected { count: self.yawn_count, started_ms: episode.started_ms, duration_ms: episode.duration_ms, peak_mar: episode.peak_mar });
+        }
+
+        if let Some((new_state, started_at)) = self.debouncer.observe(raw_state, timestamp_ms) {
+            self.commit(new_state, started_at, &mut events);
+        }
+
+        // 정해진 주기마다 종합 집중도 점수를 계산해 남깁니다.
+        self.score.add((features.ear_left + features.ear_right) / 2.0, features.mar, features.head_yaw - thresholds.yaw_center);
+        let time_in_state = Duration::from_millis(timestamp_ms.saturating_sub(self.state_since_ms));
+        let blink_rate = self.eyes.metrics(timestamp_ms).blink_rate_per_min;
+        if let Some((score, components)) = self.score.take(timestamp_ms, &thresholds, blink_rate, self.state, time_in_state) {
+            events.push(EngineEvent::AttentionScore { score, components });
+        }
+        events
+    }
+
+    // 클라이언트의 상태 신호(얼굴 미감지, 일시정지, 재개)를 반영합니다.
+    pub fn process_status(&mut self, status: ClientStatus, timestamp_ms: u64) -> Vec<EngineEvent> {
+        let mut events = Vec::new();
+        match status {
+            // 얼굴 미감지는 프레임 단위 신호이므로 디바운서를 거칩니다.
+            ClientStatus::NoFaceDetected => {
+                if self.state == AttentionState::Paused { return events; }
+                self.wake(timestamp_ms, &mut events);
+                self.yawn.reset();
+                if self.is_calibrating() { return events; } // 보정 중에는 프레임과 마찬가지로 상태를 판정하지 않습니다.
+                if let Some((new_state, started_at)) = self.debouncer.observe(AttentionState::UserLeft, timestamp_ms) {
+                    self.commit(new_state, started_at, &mut events);
+                }
+            },
+            // 일시정지/재개는 사용자의 명시적인 요청이므로 즉시 반영합니다.
+            ClientStatus::Paused | ClientStatus::Resumed => {
+                let target = if status == ClientStatus::Paused { AttentionState::Paused } else { AttentionState::Focused };
+                self.last_frame_ms = timestamp_ms; // 일시정지 동안은 프레임이 없는 것이 정상이므로, 비활성 감지 시각을 새로 잽니다.
+                if target != self.state {
+                    self.yawn.reset();
+                    self.debouncer.force(target);
+                    self.commit(target, timestamp_ms, &mut events);
+                }
+            },
+        }
+        events
+    }
+
+    // 마지막 프레임 이후 설정된 시간이 지나도록 프레임이 오지 않았으면 '비활성' 상태로 전환합니다.
+    // 비활성 상태는 마지막 프레임을 받은 시각부터 시작된 것으로 기록합니다. (일시정지 중에는 프레임이 오지 않는 것이 정상이므로 제외)
+    pub fn check_inactivity(&mut self, timestamp_ms: u64) -> Vec<EngineEvent> {
+        let mut events = Vec::new();
+        let idle = Duration::from_millis(timestamp_ms.saturating_sub(self.last_frame_ms));
+        if matches!(self.state, AttentionState::Paused | AttentionState::Inactive) || idle < self.config.inactivity.timeout { return events; }
+        self.yawn.reset();
+        self.debouncer.force(AttentionState::Inactive);
+        self.commit(AttentionState::Inactive, self.last_frame_ms, &mut events);
+        events
+    }
+
+    // 가장 최근 프레임의 머리 자세를 반환합니다.
+    pub fn head_pose(&self) -> Option<HeadPose> { self.last_pose }
+
+    // 현재 윈도우 기준의 눈 관련 지표(PERCLOS, 눈 감김 빈도 등)를 반환합니다.
+    pub fn eye_metrics(&self, timestamp_ms: u64) -> EyeMetrics { self.eyes.metrics(timestamp_ms) }
+
+    // 세션이 끝날 때 호출하여, 아직 열려 있는 현재 상태의 지속 시간을 정산합니다. (끊길 때 이미 정산했으면 아무것도 남기지 않습니다.)
+    pub fn finish(&mut self, timestamp_ms: u64) -> Vec<EngineEvent> {
+        if self.suspended { return Vec::new(); }
+        let duration_ms = timestamp_ms.saturating_sub(self.state_since_ms);
+        self.state_since_ms = timestamp_ms;
+        vec![EngineEvent::StateEnded { state: self.state, duration_ms }]
+    }
+
+    // 연결이 끊겨 세션을 보관할 때 호출하여, 현재 상태를 끊긴 시각까지로 정산합니다. 끊겨 있던 시간은 어떤 상태의 지속 시간에도 넣지 않습니다.
+    pub fn suspend(&mut self, timestamp_ms: u64) -> Vec<EngineEvent> {
+        let events = self.finish(timestamp_ms);
+        self.suspended = true;
+        events
+    }
+
+    // 보관했던 세션이 재접속하면, 끊기기 전의 상태를 재접속 시각부터 다시 엽니다. (끊기기 전의 판정 후보와 비활성 감지 시각은 버립니다.)
+    pub fn resume(&mut self, timestamp_ms: u64) {
+        if !self.suspended { return; }
+        self.suspended = false;
+        self.state_since_ms = timestamp_ms;
+        self.last_frame_ms = timestamp_ms;
+        self.debouncer.force(self.state);
+        self.yawn.reset();
+    }
+
+    // 프레임을 받은 시각을 기록하고, 비활성 상태였다면 프레임이 다시 들어온 것이므로 즉시 '집중' 상태로 되돌립니다.
+    fn wake(&mut self, timestamp_ms: u64, events: &mut Vec<EngineEvent>) {
+        self.last_frame_ms = timestamp_ms;
+        if self.state == AttentionState::Inactive {
+            self.debouncer.force(AttentionState::Focused);
+            self.commit(AttentionState::Focused, timestamp_ms, events);
+        }
+    }
+
+    // 상태 전환을 확정하고, 이전 상태의 종료 이벤트와 새 상태의 시작 이벤트를 순서대로 남깁니다.
+    fn commit(&mut self, new_state: AttentionState, started_at: u64, events: &mut Vec<EngineEvent>) {
+        let duration_ms = started_at.saturating_sub(self.state_since_ms);
+        events.push(EngineEvent::StateEnded { state: self.state, duration_ms });
+        events.push(EngineEvent::StateChanged { from: self.state, to: new_state, eyes: self.eyes.metrics(started_at), pose: self.last_pose });
+        self.state = new_state;
+        self.state_since_ms = started_at;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::features::{LEFT_EYE_INDICES, MOUTH_INDICES, RIGHT_EYE_INDICES};
+
+    const DISTRACTED: AttentionState = AttentionState::Distracted(DistractionKind::LookingAway);
+
+    // EAR, MAR, Head Yaw가 주어진 값이 되도록 눈/입/코/볼 랜드마크를 만듭니다. (이마와 턱이 없으므로 머리 자세는 None)
+    fn frame(ear: f64, mar: f64, yaw: f64) -> Vec<Landmark> {
+        let at = |index: u32, x: f64, y: f64| Landmark { index, x, y, z: 0.0 };
+        let eye = |indices: [u32; 6], x: f64| {
+            let points = [(0.0, 0.0), (0.3, -ear / 2.0), (0.7, -ear / 2.0), (1.0, 0.0), (0.7, ear / 2.0), (0.3, ear / 2.0)];
+            indices.into_iter().zip(points).map(move |(i, (dx, dy))| at(i, x + dx, dy))
+        };
+        let mouth = [(0.0, 0.0), (1.0, 0.0), (0.3, -mar / 2.0), (0.5, -mar / 2.0), (0.7, -mar / 2.0), (0.3, mar / 2.0), (0.5, mar / 2.0), (0.7, mar / 2.0)];
+        let mouth = MOUTH_INDICES.into_iter().zip(mouth).map(|(i, (x, y))| at(i, x, 2.0 + y));
+        let face = [at(1, (1.0 - yaw) / 2.0, 1.0), at(234, 0.0, 1.0), at(454, 1.0, 1.0)];
+        eye(RIGHT_EYE_INDICES, -1.5).chain(eye(LEFT_EYE_INDICES, 0.5)).chain(mouth).chain(face).collect()
+    }
+
+    fn focused() -> Vec<Landmark> { frame(0.3, 0.1, 0.0) }
+
+    // 주기적인 점수/깜빡임 통계를 빼고, 상태 전환과 하품 이벤트만 (타입, 관련 값)으로 남깁니다.
+    fn summary(events: &[EngineEvent]) -> Vec<(&'static str, u64)> {
+        events.iter().filter_map(|event| match event {
+            EngineEvent::StateEnded { duration_ms, .. } => Some((event.event_type(), *duration_ms)),
+            EngineEvent::StateChanged { .. } => Some((event.event_type(), 0)),
+            EngineEvent::YawnDetected { count, .. } => Some((event.event_type(), *count as u64)),
+            _ => None,
+        }).collect()
+    }
+
+    // 1초 간격 프레임들을 차례로 넣고, 프레임마다 나온 이벤트를 모아 돌려줍니다.
+    fn feed(engine: &mut AttentionEngine, start_ms: u64, frames: &[Vec<Landmark>]) -> Vec<EngineEvent> {
+        frames.iter().enumerate().flat_map(|(i, landmarks)| engine.process_frame(landmarks, start_ms + i as u64 * 1000)).collect()
+    }
+
+    #[test]
+    fn features_of_test_frame() {
+        let features = FrameFeatures::from_landmarks(&frame(0.2, 0.7, 0.4));
+        assert!((features.ear_left - 0.2).abs() < 1e-9 && (features.ear_right - 0.2).abs() < 1e-9);
+        assert!((features.mar - 0.7).abs() < 1e-9);
+        assert!((features.head_yaw - 0.4).abs() < 1e-9);
+    }
+
+    #[test]
+    fn debouncer_waits_for_votes_and_dwell() {
+        let mut debouncer = StateDebouncer::new(DebounceConfig::default(), AttentionState::Focused);
+        assert_eq!(debouncer.observe(DISTRACTED, 0), None);
+        assert_eq!(debouncer.observe(DISTRACTED, 100), None);
+        assert_eq!(debouncer.observe(DISTRACTED, 200), None); // 3표지만 유지 시간(2초)이 부족합니다.
+        assert_eq!(debouncer.observe(DISTRACTED, 2000), Some((DISTRACTED, 0))); // 후보가 처음 관측된 시각부터 시작된 것으로 봅니다.
+    }
+
+    #[test]
+    fn debouncer_needs_min_votes_in_window() {
+        let mut debouncer = StateDebouncer::new(DebounceConfig::default(), AttentionState::Focused);
+        for (raw, ts) in [(DISTRACTED, 0), (AttentionState::Focused, 1000), (DISTRACTED, 2000), (AttentionState::Focused, 3000), (DISTRACTED, 4000)] {
+            assert_eq!(debouncer.observe(raw, ts), None); // 4프레임 중 2표뿐이므로 유지 시간이 지나도 전환하지 않습니다.
+        }
+    }
+
+    #[test]
+    fn debouncer_drops_candidate_that_leaves_window() {
+        let mut debouncer = StateDebouncer::new(DebounceConfig::default(), AttentionState::Focused);
+        debouncer.observe(DISTRACTED, 0);
+        for ts in [1000, 2000, 3000, 4000] { assert_eq!(debouncer.observe(AttentionState::Focused, ts), None); }
+        assert_eq!(debouncer.candidate, None);
+        // 다시 나타난 후보는 새로 관측된 시각부터 유지 시간을 잽니다.
+        assert_eq!(debouncer.observe(DISTRACTED, 5000), None);
+        assert_eq!(debouncer.observe(DISTRACTED, 6000), None);
+        assert_eq!(debouncer.observe(DISTRACTED, 7000), Some((DISTRACTED, 5000)));
+    }
+
+    #[test]
+    fn debouncer_uses_exit_dwell_for_focused() {
+        let config = DebounceConfig { exit_dwell: Duration::from_secs(5), ..DebounceConfig::default() };
+        let mut debouncer = StateDebouncer::new(config, DISTRACTED);
+        for ts in [0, 1000, 2000, 3000, 4000] { assert_eq!(debouncer.observe(AttentionState::Focused, ts), None); }
+        assert_eq!(debouncer.observe(AttentionState::Focused, 5000), Some((AttentionState::Focused, 0)));
+    }
+
+    #[test]
+    fn looking_away_and_back() {
+        let mut engine = AttentionEngine::new(EngineConfig::default(), 0);
+        let away = frame(0.3, 0.1, 0.8);
+        let frames = [focused(), focused(), focused(), away.clone(), away.clone(), away, focused(), focused(), focused()];
+        let events = feed(&mut engine, 0, &frames);
+        assert_eq!(summary(&events), vec![
+            ("FOCUS_ENDED", 3000), ("DISTRACTION_STARTED", 0),     // 3초에 시작되어 5초에 확정
+            ("DISTRACTION_ENDED", 3000), ("FOCUS_RESTORED", 0),    // 6초에 돌아와 8초에 확정
+        ]);
+        assert_eq!(engine.state(), AttentionState::Focused);
+        assert_eq!(engine.state_since_ms(), 6000);
+    }
+
+    #[test]
+    fn single_glitch_frame_keeps_state() {
+        let mut engine = AttentionEngine::new(EngineConfig::default(), 0);
+        let frames = [focused(), frame(0.3, 0.1, 0.8), focused(), focused(), focused()];
+        assert_eq!(summary(&feed(&mut engine, 0, &frames)), vec![]);
+    }
+
+    #[test]
+    fn drowsiness_from_perclos() {
+        let mut engine = AttentionEngine::new(EngineConfig::default(), 0);
+        let frames = vec![frame(0.1, 0.1, 0.0); 12];
+        let events = feed(&mut engine, 0, &frames);
+        // PERCLOS는 10프레임(9초)이 모여야 계산되고, 그때부터 2초 유지 후 졸음으로 확정됩니다.
+        assert_eq!(summary(&events), vec![("FOCUS_ENDED", 9000), ("DROWSINESS_STARTED", 0)]);
+        let Some(EngineEvent::StateChanged { eyes, .. }) = events.iter().find(|e| matches!(e, EngineEvent::StateChanged { .. })) else { unreachable!() };
+        assert_eq!(eyes.perclos, 1.0);
+        assert_eq!(eyes.blink_rate_per_min, None); // 1초 간격 프레임으로는 깜빡임을 잴 수 없습니다.
+    }
+
+    #[test]
+    fn yawns_are_counted_once_per_episode() {
+        let mut engine = AttentionEngine::new(EngineConfig::default(), 0);
+        let open = frame(0.3, 0.8, 0.0);
+        let frames = [focused(), open.clone(), open.clone(), open.clone(), focused(), open.clone(), focused(), open.clone(), open.clone(), open, focused()];
+        let events = feed(&mut engine, 0, &frames);
+        // 1초만 벌어진 구간(말하기)은 세지 않습니다.
+        assert_eq!(summary(&events), vec![("YAWN_DETECTED", 1), ("YAWN_DETECTED", 2)]);
+        let Some(EngineEvent::YawnDetected { started_ms, duration_ms, .. }) = events.iter().find(|e| matches!(e, EngineEvent::YawnDetected { .. })) else { unreachable!() };
+        assert_eq!((*started_ms, *duration_ms), (1000, 3000));
+    }
+
+    #[test]
+    fn inactivity_starts_at_last_frame_and_ends_on_next_frame() {
+        let mut engine = AttentionEngine::new(EngineConfig::default(), 0);
+        feed(&mut engine, 0, &[focused(), focused()]);
+        assert_eq!(summary(&engine.check_inactivity(15_000)), vec![]);
+        assert_eq!(summary(&engine.check_inactivity(16_000)), vec![("FOCUS_ENDED", 1000), ("INACTIVITY_STARTED", 0)]);
+        assert_eq!(engine.state_since_ms(), 1000);
+        assert_eq!(summary(&engine.process_frame(&focused(), 20_000)), vec![("INACTIVITY_ENDED", 19_000), ("FOCUS_RESTORED", 0)]);
+    }
+
+    #[test]
+    fn pause_skips_frames_until_resumed() {
+        let mut engine = AttentionEngine::new(EngineConfig::default(), 0);
+        assert_eq!(summary(&engine.process_status(ClientStatus::Paused, 1000)), vec![("FOCUS_ENDED", 1000), ("SESSION_PAUSED", 0)]);
+        assert!(feed(&mut engine, 2000, &vec![frame(0.3, 0.1, 0.8); 5]).is_empty());
+        assert_eq!(summary(&engine.check_inactivity(60_000)), vec![]);
+        assert_eq!(summary(&engine.process_status(ClientStatus::Resumed, 61_000)), vec![("PAUSE_ENDED", 60_000), ("SESSION_RESUMED", 0)]);
+    }
+
+    #[test]
+    fn calibration_collects_baseline_then_applies_thresholds() {
+        let mut engine = AttentionEngine::new(EngineConfig::default(), 0);
+        assert_eq!(engine.start_calibration(0), vec![EngineEvent::CalibrationStarted]);
+        // 보정 중에는 고개를 돌린 프레임도 상태 판정에 쓰지 않습니다.
+        let events = feed(&mut engine, 0, &vec![frame(0.35, 0.2, 0.4); 11]);
+        assert_eq!(summary(&events), vec![]);
+        let completed: Vec<_> = events.iter().filter_map(|e| match e { EngineEvent::CalibrationCompleted { thresholds, .. } => Some(*thresholds), _ => None }).collect();
+        assert_eq!(completed.len(), 1);
+        assert!(!engine.is_calibrating());
+        assert!((completed[0].ear_enter - 0.21 * 1.25).abs() < 1e-9);
+        assert!((completed[0].yaw_center - 0.4).abs() < 1e-9);
+        // 보정한 방향이 정면이 되므로, 같은 자세를 유지해도 주의 분산이 아닙니다.
+        assert_eq!(summary(&feed(&mut engine, 11_000, &vec![frame(0.35, 0.2, 0.4); 5])), vec![]);
+    }
+
+    #[test]
+    fn stored_calibration_follows_reconfigured_thresholds() {
+        let mut engine = AttentionEngine::new(EngineConfig::default(), 0);
+        engine.apply_calibration(Calibration { ear_mean: 0.28, ear_std: 0.0, mar_mean: 0.1, mar_std: 0.0, yaw_mean: 0.2, yaw_std: 0.0, pitch_mean: 0.0, pitch_std: 0.0, samples: 10 });
+        let mut config = EngineConfig::default();
+        config.thresholds.yaw_enter = 0.5;
+        engine.reconfigure(config);
+        assert_eq!((engine.thresholds().yaw_center, engine.thresholds().yaw_enter), (0.2, 0.5));
+        // 기준 방향에서 0.4만큼 돌린 자세는 새 허용 범위(0.5) 안입니다.
+        assert_eq!(summary(&feed(&mut engine, 0, &vec![frame(0.3, 0.1, 0.6); 5])), vec![]);
+    }
+
+    #[test]
+    fn no_face_during_calibration_keeps_state() {
+        let mut engine = AttentionEngine::new(EngineConfig::default(), 0);
+        engine.start_calibration(0);
+        let events: Vec<_> = (0..5).flat_map(|i| engine.process_status(ClientStatus::NoFaceDetected, i * 1000)).collect();
+        assert_eq!(summary(&events), vec![]);
+        assert_eq!(engine.state(), AttentionState::Focused);
+        assert!(engine.is_calibrating());
+    }
+
+    #[test]
+    fn blink_stats_only_when_blinks_are_measurable() {
+        let blink_stats = |events: &[EngineEvent]| events.iter().filter(|e| matches!(e, EngineEvent::BlinkStats { .. })).count();
+        // 1초 간격 프레임으로는 깜빡임을 잴 수 없으므로 BLINK_STATS를 보내지 않습니다.
+        let mut engine = AttentionEngine::new(EngineConfig::default(), 0);
+        assert_eq!(blink_stats(&feed(&mut engine, 0, &vec![focused(); 130])), 0);
+        // 100ms 간격이면 주기(60초)마다 보냅니다.
+        let mut engine = AttentionEngine::new(EngineConfig::default(), 0);
+        let events: Vec<_> = (0..1300).flat_map(|i| engine.process_frame(&focused(), i * 100)).collect();
+        assert_eq!(blink_stats(&events), 2);
+    }
+
+    #[test]
+    fn disconnected_gap_is_not_counted_in_state_duration() {
+        let focused_ended = AttentionState::Focused.ended_event_type();
+        let mut engine = AttentionEngine::new(EngineConfig::default(), 0);
+        feed(&mut engine, 0, &vec![focused(); 5]);
+        // 끊긴 시각(5초)까지로 상태를 정산하고, 보관 중에 만료/종료되어도 다시 정산하지 않습니다.
+        assert_eq!(summary(&engine.suspend(5000)), vec![(focused_ended, 5000)]);
+        assert_eq!(summary(&engine.finish(60000)), vec![]);
+        // 60초 뒤에 재접속하면 그 시각부터 상태를 다시 엽니다.
+        engine.resume(65000);
+        assert_eq!(engine.state_since_ms(), 65000);
+        assert_eq!(summary(&engine.check_inactivity(65000)), vec![]);
+        feed(&mut engine, 65000, &vec![focused(); 3]);
+        assert_eq!(summary(&engine.finish(70000)), vec![(focused_ended, 5000)]);
+    }
+}
@@ -0,0 +1,226 @@
+// --- 눈 감김 추적 (PERCLOS, 깜빡임) ---
+// 프레임마다 계산한 EAR 값으로 눈이 감겨 있는지를 판정하고, 최근 일정 시간(슬라이딩 윈도우) 동안의
+// 눈 감김 비율(PERCLOS), 분당 깜빡임 횟수, 평균 깜빡임 시간을 계산합니다. 졸음과 눈 피로 판단의 핵심 지표입니다.
+use crate::engine::Thresholds;
+use serde::Serialize; // 지표를 이벤트 payload에 담기 위해 사용합니다.
+use std::collections::VecDeque; // 윈도우 안의 프레임/눈 감김 기록을 시간순으로 보관하기 위한 큐입니다.
+use std::time::Duration; // 윈도우 길이 설정에 사용합니다.
+
+// PERCLOS 계산과, PERCLOS로 졸음 상태를 판정하는 기준 설정입니다.
+#[derive(Debug, Clone, Copy)]
+pub struct PerclosConfig {
+    pub window: Duration,   // PERCLOS를 계산할 슬라이딩 윈도우 길이
+    pub min_samples: usize, // PERCLOS를 신뢰하기 위해 윈도우 안에 필요한 최소 프레임 수
+    pub enter: f64,         // PERCLOS가 이 값 이상이면 '졸음'으로 판단합니다.
+    pub exit: f64,          // 졸음 상태에서는 PERCLOS가 이 값 미만으로 내려가야 졸음에서 벗어난 것으로 판단합니다.
+}
+
+impl Default for PerclosConfig {
+    fn default() -> Self {
+        PerclosConfig { window: Duration::from_secs(60), min_samples: 10, enter: 0.15, exit: 0.08 }
+    }
+}
+
+// 깜빡임 판정과 BLINK_STATS 이벤트 발행 주기 설정입니다.
+#[derive(Debug, Clone, Copy)]
+pub struct BlinkConfig {
+    pub max_duration: Duration,   // 눈을 감았다 뜨기까지 이 시간 이내여야 깜빡임으로 봅니다. (더 길면 졸음성 눈 감김)
+    pub max_frame_gap: Duration,  // 평균 프레임 간격이 이보다 길면 깜빡임(100~400ms)을 놓치므로 깜빡임 빈도와 시간을 계산하지 않습니다.
+    pub stats_interval: Duration, // BLINK_STATS 이벤트를 발행하는 주기
+}
+
+impl Default for BlinkConfig {
+    fn default() -> Self {
+        BlinkConfig { max_duration: Duration::from_millis(500), max_frame_gap: Duration::from_millis(150), stats_interval: Duration::from_secs(60) }
+    }
+}
+
+// 최근 윈도우 기준의 눈 관련 지표입니다.
+// 프레임이 깜빡임을 잡을 만큼 자주 들어오지 않으면 깜빡임 빈도와 시간은 None(null)입니다.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct EyeMetrics {
+    pub perclos: f64,                        // 윈도우 안에서 눈이 감겨 있던 프레임의 비율 (0.0 ~ 1.0)
+    pub blink_rate_per_min: Option<f64>,     // 분당 깜빡임 횟수
+    pub mean_blink_duration_ms: Option<f64>, // 평균 깜빡임(눈 감김) 시간
+    pub total_blinks: u32,                   // 세션 전체의 누적 깜빡임 횟수
+}
+
+// 눈을 감았다가 다시 뜬 한 번의 구간입니다.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClosureEpisode {
+    pub started_ms: u64,
+    pub duration_ms: u64,
+    pub is_blink: bool, // 깜빡임으로 볼 만큼 짧았는지 여부
+}
+
+// 프레임별 눈 감김 여부와 눈 감김 구간을 윈도우 단위로 보관하는 추적기입니다.
+#[derive(Debug, Clone)]
+pub struct EyeClosureTracker {
+    config: PerclosConfig,
+    blink: BlinkConfig,
+    samples: VecDeque<(u64, bool)>,        // (프레임 시각, 눈 감김 여부)
+    blinks: VecDeque<ClosureEpisode>,      // 윈도우 안에서 끝난 깜빡임 구간들
+    closed_since: Option<u64>,             // 현재 눈을 감고 있다면, 감기 시작한 시각
+    total_blinks: u32,                     // 세션 전체의 누적 깜빡임 횟수
+}
+
+impl EyeClosureTracker {
+    pub fn new(config: PerclosConfig, blink: BlinkConfig) -> Self {
+        EyeClosureTracker { config, blink, samples: VecDeque::new(), blinks: VecDeque::new(), closed_since: None, total_blinks: 0 }
+    }
+
+    // 지금까지 모은 프레임과 깜빡임 기록은 유지한 채 설정만 바꿉니다. (설정 리로드)
+    pub fn set_config(&mut self, config: PerclosConfig, blink: BlinkConfig) { self.config = config; self.blink = blink; }
+
+    // 현재 눈을 감고 있는지 여부를 반환합니다.
+    pub fn is_closed(&self) -> bool { self.closed_since.is_some() }
+
+    // 한 프레임의 양쪽 EAR을 반영합니다. 눈을 감았다가 다시 뜬 순간에는 끝난 눈 감김 구간을 반환합니다.
+    // 두 눈이 모두 ear_enter 아래로 내려가면 감김이 시작되고, 한쪽이라도 ear_exit 이상으로 회복되면 감김이 끝납니다. (히스테리시스)
+    pub fn update(&mut self, ear_left: f64, ear_right: f64, thresholds: &Thresholds, timestamp_ms: u64) -> Option<ClosureEpisode> {
+        let ear = ear_left.max(ear_right);
+        let mut finished = None;
+        match self.closed_since {
+            None if ear < thresholds.ear_enter => self.closed_since = Some(timestamp_ms),
+            Some(started_ms) if ear >= thresholds.ear_exit => {
+                let duration_ms = timestamp_ms.saturating_sub(started_ms);
+                let episode = ClosureEpisode { started_ms, duration_ms, is_blink: duration_ms <= self.blink.max_duration.as_millis() as u64 };
+                if episode.is_blink {
+                    self.blinks.push_back(episode);
+                    self.total_blinks += 1;
+                }
+                self.closed_since = None;
+                finished = Some(episode);
+            },
+            _ => {}
+        }
+        self.samples.push_back((timestamp_ms, self.closed_since.is_some()));
+        self.prune(timestamp_ms);
+        finished
+    }
+
+    // 윈도우 안의 프레임 수가 충분하면 PERCLOS를 반환합니다.
+    pub fn perclos(&self) -> Option<f64> {
+        if self.samples.len() < self.config.min_samples { return None; }
+        let closed = self.samples.iter().filter(|(_, closed)| *closed).count();
+        Some(closed as f64 / self.samples.len() as f64)
+    }
+
+    // PERCLOS 기준으로 졸음 상태인지 판정합니다. 현재 졸음 상태라면 해제 기준(exit)을 적용합니다.
+    pub fn is_drowsy(&self, currently_drowsy: bool) -> bool {
+        let threshold = if currently_drowsy { self.config.exit } else { self.config.enter };
+        self.perclos().is_some_and(|perclos| perclos >= threshold)
+    }
+
+    // 현재 윈도우 기준의 눈 관련 지표를 계산합니다.
+    pub fn metrics(&self, now_ms: u64) -> EyeMetrics {
+        let span_ms = self.samples.front().map(|(oldest, _)| now_ms.saturating_sub(*oldest)).unwrap_or(0);
+        let measurable = self.blinks_measurable();
+        let blink_rate_per_min = (measurable && span_ms > 0).then(|| self.blinks.len() as f64 * 60_000.0 / span_ms as f64);
+        let mean_blink_duration_ms = if !measurable {
+            None
+        } else if self.blinks.is_empty() {
+            Some(0.0)
+        } else {
+            Some(self.blinks.iter().map(|e| e.duration_ms as f64).sum::<f64>() / self.blinks.len() as f64)
+        };
+        EyeMetrics { perclos: self.perclos().unwrap_or(0.0), blink_rate_per_min, mean_blink_duration_ms, total_blinks: self.total_blinks }
+    }
+
+    // 윈도우 안의 평균 프레임 간격이 깜빡임을 잡을 만큼 짧은지 확인합니다.
+    fn blinks_measurable(&self) -> bool {
+        let (Some((first, _)), Some((last, _))) = (self.samples.front(), self.samples.back()) else { return false };
+        self.samples.len() >= self.config.min_samples.max(2) && last.saturating_sub(*first) / (self.samples.len() as u64 - 1) <= self.blink.max_frame_gap.as_millis() as u64
+    }
+
+    // 윈도우를 벗어난 오래된 기록을 지웁니다.
+    fn prune(&mut self, now_ms: u64) {
+        let window_start = now_ms.saturating_sub(self.config.window.as_millis() as u64);
+        while self.samples.front().is_some_and(|(ts, _)| *ts < window_start) { self.samples.pop_front(); }
+        while self.blinks.front().is_some_and(|e| e.started_ms + e.duration_ms < window_start) { self.blinks.pop_front(); }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPEN: f64 = 0.3;
+    const CLOSED: f64 = 0.1;
+
+    fn tracker() -> EyeClosureTracker { EyeClosureTracker::new(PerclosConfig::default(), BlinkConfig::default()) }
+
+    // (시각, EAR) 프레임들을 차례로 넣습니다.
+    fn feed(tracker: &mut EyeClosureTracker, frames: impl IntoIterator<Item = (u64, f64)>) {
+        for (ts, ear) in frames { tracker.update(ear, ear, &Thresholds::default(), ts); }
+    }
+
+    #[test]
+    fn perclos_needs_min_samples() {
+        let mut tracker = tracker();
+        feed(&mut tracker, (0..9).map(|i| (i * 1000, CLOSED)));
+        assert_eq!(tracker.perclos(), None);
+        feed(&mut tracker, [(9000, OPEN)]);
+        assert_eq!(tracker.perclos(), Some(0.9));
+    }
+
+    #[test]
+    fn perclos_counts_closed_frames_in_window() {
+        let mut tracker = tracker();
+        feed(&mut tracker, (0..20).map(|i| (i * 1000, if i % 4 == 0 { CLOSED } else { OPEN })));
+        assert_eq!(tracker.perclos(), Some(0.25));
+        // 윈도우(60초)를 벗어난 프레임은 빠집니다.
+        feed(&mut tracker, (80..100).map(|i| (i * 1000, OPEN)));
+        assert_eq!(tracker.perclos(), Some(0.0));
+    }
+
+    #[test]
+    fn drowsy_uses_enter_and_exit_thresholds() {
+        let mut tracker = tracker();
+        feed(&mut tracker, (0..10).map(|i| (i * 1000, if i == 0 { CLOSED } else { OPEN }))); // PERCLOS 0.1
+        assert!(!tracker.is_drowsy(false));
+        assert!(tracker.is_drowsy(true));
+    }
+
+    #[test]
+    fn eyes_need_both_closed_and_one_reopened() {
+        let mut tracker = tracker();
+        let thresholds = Thresholds::default();
+        tracker.update(CLOSED, OPEN, &thresholds, 0);
+        assert!(!tracker.is_closed());
+        tracker.update(CLOSED, CLOSED, &thresholds, 100);
+        assert!(tracker.is_closed());
+        assert_eq!(tracker.update(0.22, 0.22, &thresholds, 200), None); // 해제 임계값(0.24) 아래에서는 계속 감긴 것으로 봅니다.
+        assert_eq!(tracker.update(CLOSED, OPEN, &thresholds, 300), Some(ClosureEpisode { started_ms: 100, duration_ms: 200, is_blink: true }));
+    }
+
+    #[test]
+    fn blink_metrics_at_high_frame_rate() {
+        let mut tracker = tracker();
+        // 100ms 간격으로 10초 동안, 2초마다 200ms씩 눈을 감습니다.
+        feed(&mut tracker, (0..100).map(|i| (i * 100, if i % 20 < 2 && i >= 20 { CLOSED } else { OPEN })));
+        let metrics = tracker.metrics(9900);
+        assert_eq!(metrics.total_blinks, 4);
+        assert_eq!(metrics.mean_blink_duration_ms, Some(200.0));
+        assert!((metrics.blink_rate_per_min.unwrap() - 4.0 * 60_000.0 / 9900.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn long_closure_is_not_a_blink() {
+        let mut tracker = tracker();
+        feed(&mut tracker, (0..30).map(|i| (i * 100, if (5..15).contains(&i) { CLOSED } else { OPEN })));
+        let metrics = tracker.metrics(2900);
+        assert_eq!(metrics.total_blinks, 0);
+        assert_eq!(metrics.mean_blink_duration_ms, Some(0.0));
+    }
+
+    #[test]
+    fn blink_metrics_withheld_at_low_frame_rate() {
+        let mut tracker = tracker();
+        feed(&mut tracker, (0..20).map(|i| (i * 1000, if i % 5 == 0 { CLOSED } else { OPEN })));
+        let metrics = tracker.metrics(19_000);
+        assert_eq!((metrics.blink_rate_per_min, metrics.mean_blink_duration_ms), (None, None));
+        assert_eq!(metrics.perclos, 0.2);
+    }
+}
@@ -0,0 +1,78 @@
+// --- 특징 계산 헬퍼(도우미) 함수들 ---
+// 이 모듈의 함수들은 I/O 없이 순수하게 계산만 담당하는 보조 함수들입니다.
+use serde::{Deserialize, Serialize}; // 클라이언트가 보낸 JSON 랜드마크를 Rust 구조체로 변환하고, 머리 자세를 이벤트에 담습니다.
+use std::collections::HashMap; // 랜드마크 인덱스를 키(Key)로, 랜드마크 데이터를 값(Value)으로 저장하기 위한 해시맵 자료구조입니다.
+
+// 클라이언트가 보내는 랜드마크 하나의 데이터 구조입니다. (z는 MediaPipe가 추정한 상대 깊이로, 머리 자세 추정에 사용합니다.)
+#[derive(Deserialize, Serialize, Debug, Clone, Copy)]
+pub struct Landmark { pub index: u32, pub x: f64, pub y: f64, pub z: f64 }
+
+// MediaPipe Face Mesh 기준, 각 특징 계산에 사용하는 랜드마크 인덱스 목록입니다.
+pub const LEFT_EYE_INDICES: [u32; 6] = [362, 385, 387, 263, 373, 380];
+pub const RIGHT_EYE_INDICES: [u32; 6] = [33, 160, 158, 133, 153, 144];
+pub const MOUTH_INDICES: [u32; 8] = [61, 291, 13, 81, 178, 14, 311, 402];
+pub const FOREHEAD_INDEX: u32 = 10;
+pub const CHIN_INDEX: u32 = 152;
+pub const RIGHT_EYE_OUTER_INDEX: u32 = 33;  // 화면 기준 왼쪽에 보이는 눈(사용자의 오른쪽 눈)의 바깥 눈꼬리
+pub const LEFT_EYE_OUTER_INDEX: u32 = 263;  // 화면 기준 오른쪽에 보이는 눈(사용자의 왼쪽 눈)의 바깥 눈꼬리
+
+// 랜드마크로 추정한 머리 자세입니다. 모든 각도는 도(degree) 단위입니다.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct HeadPose {
+    pub yaw: f64,   // 좌우 회전 (양수: 화면 기준 오른쪽으로 돌림)
+    pub pitch: f64, // 상하 기울기 (양수: 고개를 숙임)
+    pub roll: f64,  // 좌우 기울기 (양수: 화면 기준 시계 방향으로 기울임)
+}
+
+// 한 프레임에서 계산한 주요 특징 값들의 묶음입니다.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameFeatures {
+    pub ear_left: f64,  // 왼쪽 눈 개방 비율
+    pub ear_right: f64, // 오른쪽 눈 개방 비율
+    pub mar: f64,       // 입 개방 비율
+    pub head_yaw: f64,  // 고개 좌우 회전 정도 (-1.0 ~ 1.0)
+    pub pose: Option<HeadPose>, // 3차원 머리 자세 (필요한 랜드마크가 없으면 None)
+}
+
+impl FrameFeatures {
+    // 랜드마크 목록에서 EAR, MAR, Head Yaw 등 주요 특징 값을 한 번에 계산합니다.
+    pub fn from_landmarks(landmarks: &[Landmark]) -> Self {
+        // 랜드마크 데이터를 인덱스로 빠르게 찾기 위해 해시맵으로 변환합니다.
+        let landmarks_map: HashMap<u32, Landmark> = landmarks.iter().map(|&lm| (lm.index, lm)).collect();
+        FrameFeatures {
+            ear_left: get_ear(&get_landmarks_by_indices(&landmarks_map, &LEFT_EYE_INDICES)),
+            ear_right: get_ear(&get_landmarks_by_indices(&landmarks_map, &RIGHT_EYE_INDICES)),
+            mar: get_mar(&get_landmarks_by_indices(&landmarks_map, &MOUTH_INDICES)),
+            head_yaw: get_head_yaw(&landmarks_map),
+            pose: get_head_pose(&landmarks_map),
+        }
+    }
+}
+
+// 두 랜드마크 사이의 2D 거리를 유클리드 공식으로 계산합니다.
+pub fn get_distance(p1: &Landmark, p2: &Landmark) -> f64 { ((p1.x - p2.x).powi(2) + (p1.y - p2.y).powi(2)).sqrt() }
+// 눈의 랜드마크 6개를 받아 눈의 개방 비율(EAR)을 계산하여 졸음을 판단합니다.
+pub fn get_ear(eye_landmarks: &[Landmark]) -> f64 { if eye_landmarks.len() < 6 { return 0.0; } let ver_dist1 = get_distance(&eye_landmarks[1], &eye_landmarks[5]); let ver_dist2 = get_distance(&eye_landmarks[2], &eye_landmarks[4]); let hor_dist = get_distance(&eye_landmarks[0], &eye_landmarks[3]); if hor_dist == 0.0 { return 0.0; } (ver_dist1 + ver_dist2) / (2.0 * hor_dist) }
+// 입의 랜드마크 8개를 받아 입의 개방 비율(MAR)을 계산하여 하품을 판단합니다.
+pub fn get_mar(mouth_landmarks: &[Landmark]) -> f64 { if mouth_landmarks.len() < 8 { return 0.0; } let ver_dist1 = get_distance(&mouth_landmarks[2], &mouth_landmarks[5]); let ver_dist2 = get_distance(&mouth_landmarks[3], &mouth_landmarks[6]); let ver_dist3 = get_distance(&mouth_landmarks[4], &mouth_landmarks[7]); let hor_dist = get_distance(&mouth_landmarks[0], &mouth_landmarks[1]); if hor_dist == 0.0 { return 0.0; } (ver_dist1 + ver_dist2 + ver_dist3) / (3.0 * hor_dist) }
+// 코와 양 볼의 랜드마크를 이용해 고개의 좌우 회전(Yaw) 정도를 추정하여 주의 분산을 판단합니다.
+pub fn get_head_yaw(landmarks_map: &HashMap<u32, Landmark>) -> f64 { if let (Some(&nose), Some(&left_cheek), Some(&right_cheek)) = (landmarks_map.get(&1), landmarks_map.get(&234), landmarks_map.get(&454)) { let dist_left = (nose.x - left_cheek.x).abs(); let dist_right = (right_cheek.x - nose.x).abs(); if (dist_left + dist_right) == 0.0 { return 0.0; } (dist_right - dist_left) / (dist_left + dist_right) } else { 0.0 } }
+// 양쪽 눈꼬리, 이마, 턱 랜드마크의 3차원 좌표(z 포함)로 머리의 Yaw/Pitch/Roll 각도를 추정합니다.
+// 눈꼬리를 잇는 선은 좌우 축, 턱에서 이마로 향하는 선은 상하 축으로 보고, 각 축이 기울어진 정도를 각도로 계산합니다.
+pub fn get_head_pose(landmarks_map: &HashMap<u32, Landmark>) -> Option<HeadPose> {
+    let (right_eye, left_eye) = (landmarks_map.get(&RIGHT_EYE_OUTER_INDEX)?, landmarks_map.get(&LEFT_EYE_OUTER_INDEX)?);
+    let (forehead, chin) = (landmarks_map.get(&FOREHEAD_INDEX)?, landmarks_map.get(&CHIN_INDEX)?);
+    let (hx, hy, hz) = (left_eye.x - right_eye.x, left_eye.y - right_eye.y, left_eye.z - right_eye.z); // 좌우 축
+    let (vy, vz) = (forehead.y - chin.y, forehead.z - chin.z); // 상하 축 (화면 y축은 아래쪽이 양수)
+    if (hx == 0.0 && hz == 0.0) || (vy == 0.0 && vz == 0.0) { return None; }
+    Some(HeadPose {
+        yaw: (-hz).atan2(hx).to_degrees(),   // 고개를 돌리면 한쪽 눈꼬리가 카메라에 가까워집니다.
+        pitch: (-vz).atan2(-vy).to_degrees(), // 고개를 숙이면 이마가 턱보다 카메라에 가까워집니다. (z가 작을수록 카메라에 가까움)
+        roll: hy.atan2(hx).to_degrees(),     // 고개를 기울이면 두 눈꼬리의 높이가 달라집니다.
+    })
+}
+
+// 전체 랜드마크 해시맵에서, 필요한 인덱스의 랜드마크들만 효율적으로 뽑아서 벡터로 반환하는 함수입니다.
+pub fn get_landmarks_by_indices(map: &HashMap<u32, Landmark>, indices: &[u32]) -> Vec<Landmark> {
+    indices.iter().filter_map(|&i| map.get(&i).copied()).collect()
+}
@@ -0,0 +1,8 @@
+// --- 집중도 분석 라이브러리 ---
+// 웹소켓 서버(main.rs)와 배치 재분석 도구가 함께 사용하는, I/O 없는 분석 로직을 모아둔 라이브러리 크레이트입니다.
+pub mod calibration; // 사용자별 EAR/MAR/Yaw 기준값 보정
+pub mod engine;      // 상태 머신과 AttentionEngine
+pub mod eyes;        // PERCLOS, 눈 감김 빈도 등 눈 감김 지표
+pub mod features;    // EAR, MAR, Head Yaw 등 특징 계산 함수
+pub mod score;       // 종합 집중도 점수
+pub mod yawn;        // 하품 구간 감지
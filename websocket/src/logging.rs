@@ -0,0 +1,18 @@
+// --- 로그 출력 ---
+// 서버 로그는 보통 표준 출력에 씁니다. stdout 출력 대상이 켜져 있으면 표준 출력에는 이벤트(NDJSON)만 나가야
+// 다른 프로그램으로 그대로 넘길 수 있으므로, 로그를 표준 에러로 보냅니다.
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+// 이후의 로그를 표준 에러로 보냅니다.
+pub fn use_stderr() { TO_STDERR.store(true, Ordering::Relaxed); }
+
+pub fn to_stderr() -> bool { TO_STDERR.load(Ordering::Relaxed) }
+
+// println!과 같은 형식으로 로그 한 줄을 씁니다. (에러 로그는 그대로 eprintln!을 씁니다.)
+macro_rules! log {
+    ($($arg:tt)*) => {
+        if $crate::logging::to_stderr() { eprintln!($($arg)*) } else { println!($($arg)*) }
+    };
+}
@@ -1,47 +1,46 @@
+#[macro_use]
+mod logging; // 서버 로그 출력입니다. stdout 출력 대상이 켜져 있으면 표준 에러로 보냅니다. (src/logging.rs)
+mod auth; // 웹소켓 핸드셰이크 토큰 인증입니다. (src/auth.rs)
+mod catalog; // 다국어 알람 문구 카탈로그입니다. (src/catalog.rs)
+mod config; // TOML 파일과 환경 변수에서 읽는 서버 설정입니다. (src/config.rs)
+mod outbox; // 출력 대상에 보내지 못한 이벤트를 보관했다가 순서대로 다시 보내는 대기열입니다. (src/outbox.rs)
+mod protocol; // 클라이언트 ↔ 서버 메시지 형식과 프로토콜 버전 협상입니다. (src/protocol.rs)
+mod sessions; // 재접속한 클라이언트가 이어서 쓸 수 있도록 끊긴 세션을 보관합니다. (src/sessions.rs)
+mod sinks; // 이벤트 출력 대상(Redis, 파일, 표준 출력, 웹훅)입니다. (src/sinks.rs)
+
 // --- 외부 라이브러리(Crate) 가져오기 ---
 // 이 섹션에서는 프로젝트에 필요한 모든 외부 라이브러리들을 선언합니다.
-use serde::{Deserialize, Serialize}; // JSON 데이터를 Rust 구조체로 자동 변환하거나, 그 반대의 작업을 수행합니다.
+use serde::Serialize; // Rust 구조체를 JSON 데이터로 자동 변환합니다.
 use serde_json::{json, Value}; // JSON 데이터를 좀 더 유연하게 다루기 위한 기능들을 제공합니다.
-use std::collections::HashMap; // 랜드마크 인덱스를 키(Key)로, 랜드마크 데이터를 값(Value)으로 저장하기 위한 해시맵 자료구조입니다.
-use std::env; // REDIS_HOST와 같은 시스템 환경 변수를 읽어오기 위해 사용합니다.
+use std::collections::HashMap; // 알람 종류별 마지막 전송 시각을 기록하기 위해 사용합니다. (알람 쿨다운)
+use std::sync::Arc; // 알람 문구 카탈로그처럼 모든 연결이 함께 읽는 데이터를 공유하기 위해 사용합니다.
+use std::process; // 설정 오류 등으로 서버를 시작하지 못하면 0이 아닌 종료 코드로 끝냅니다. (컨테이너 재시작 정책이 실패로 인식)
 use tokio::net::{TcpListener, TcpStream}; // 비동기(Non-blocking) 방식으로 네트워크 연결을 처리하기 위한 Tokio 라이브러리입니다.
-use tokio_tungstenite::{accept_async, tungstenite::protocol::Message}; // 비동기 웹소켓 프로토콜 통신을 구현하기 위한 라이브러리입니다.
+use tokio_tungstenite::{accept_hdr_async, tungstenite::handshake::server::{ErrorResponse, Request, Response}, tungstenite::http::{HeaderValue, StatusCode}, tungstenite::protocol::{frame::coding::CloseCode, CloseFrame, Message}}; // 비동기 웹소켓 프로토콜 통신을 구현하기 위한 라이브러리입니다.
 use futures_util::{StreamExt, SinkExt}; // 웹소켓과 같은 비동기 데이터 스트림을 더 편리하게 다루기 위한 유틸리티입니다.
 use redis::AsyncCommands; // Redis에 비동기적으로 명령(publish, set, get 등)을 보내기 위해 사용합니다.
 use tokio::signal; // Ctrl+C와 같은 시스템 종료 신호를 감지하여 서버를 안전하게 종료시키기 위해 사용합니다.
 use tokio::signal::unix::{signal, SignalKind}; // 유닉스 계열 시스템의 특정 신호(SIGHUP 등)를 처리하기 위한 모듈입니다.
-use std::time::{Duration, Instant}; // 상태 변화 시간 측정 등 시간 관련 처리를 위해 사용합니다.
+use std::time::Duration; // Ping 주기 등 시간 관련 처리를 위해 사용합니다.
+use tokio::task::JoinSet; // 종료 시 모든 연결 작업이 끝나기를 기다리기 위해 사용합니다.
+use tokio::sync::watch; // SIGHUP으로 다시 읽은 설정을 실행 중인 모든 연결에 한꺼번에 전달하기 위해 사용합니다.
+use tokio::sync::mpsc; // 같은 세션으로 다시 접속한 연결이 기존 연결에 세션을 넘겨달라고 요청하는 채널입니다.
 use tokio::time::interval; // 주기적으로 Ping 메시지를 보내는 등 정해진 간격으로 작업을 수행하기 위해 사용합니다.
 use chrono::Utc; // 이벤트 발생 시각을 UTC 국제 표준시로 기록하기 위해 사용합니다.
+use auth::{token_from_request, AuthenticatedUser, Authenticator};
+use catalog::{AlarmCatalog, FALLBACK_LANGUAGE};
+use config::{AlarmPolicy, RedisConfig, ServerConfig, SinkKind};
+use outbox::Outbox;
+use sinks::SharedRedis;
+use sessions::{Claim, Handover, SessionIdentity, SessionStore};
+use protocol::{decode_landmark_frame, negotiate, AlarmCode, ClientEvent, ClientMessage, Negotiated, OutboundMessage, ProtocolError, Severity, CAP_SCORE, CAP_STATE};
+use websocket::calibration::Calibration; // 사용자별 기준값 보정 결과입니다.
+use websocket::engine::{AttentionEngine, AttentionState, ClientStatus, DistractionKind, EngineEvent}; // I/O 없는 집중도 분석 엔진입니다. (src/lib.rs)
 
 // --- 데이터 구조체 정의 ---
-// 이 섹션에서는 클라이언트와 서버가 주고받는 JSON 데이터의 형식을 Rust 구조체로 정의합니다.
-
-// 클라이언트가 보내는 랜드마크 하나의 데이터 구조입니다.
-#[derive(Deserialize, Debug, Clone, Copy)]
-struct Landmark { index: u32, x: f64, y: f64, z: f64 }
-
-// 랜드마크 목록 전체를 담는 데이터 구조입니다.
-#[derive(Deserialize, Debug)]
-struct DataPayload { landmarks: Vec<Landmark> }
-
-// 클라이언트의 특정 상태(얼굴 미감지, 일시정지 등)를 전달하기 위한 구조체입니다.
-#[derive(Deserialize, Debug)]
-struct StatusPayload { status: String }
+// 클라이언트와 주고받는 메시지 형식은 protocol 모듈에 있고, 이 섹션에서는 출력 대상(Redis 등)에 보내는 이벤트 형식을 정의합니다.
 
-// 클라이언트로부터 받는 모든 메시지의 기본 형식입니다. `serde(rename = ...)`는 JSON의 키 이름과 Rust 변수 이름을 매핑합니다.
-#[derive(Deserialize, Debug, Clone)]
-struct ClientMessage {
-    #[serde(rename = "sessionId")]
-    session_id: String,
-    #[serde(rename = "userId")]
-    user_id: String,
-    #[serde(rename = "eventType")]
-    event_type: String,
-    payload: Value,
-}
-
-// 서버가 Redis에 발행(Publish)하는 이벤트의 표준 형식입니다.
+// 서버가 출력 대상(Redis 등)에 보내는 이벤트의 표준 형식입니다.
 #[derive(Serialize, Debug)]
 struct ServerEvent<'a> {
     #[serde(rename = "sessionId")]
@@ -55,35 +54,61 @@ struct ServerEvent<'a> {
 }
 
 
-// --- 특징 계산 헬퍼(도우미) 함수들 ---
-// 이 섹션의 함수들은 순수하게 계산만 담당하는 보조 함수들입니다.
-
-// 두 랜드마크 사이의 2D 거리를 유클리드 공식으로 계산합니다.
-fn get_distance(p1: &Landmark, p2: &Landmark) -> f64 { ((p1.x - p2.x).powi(2) + (p1.y - p2.y).powi(2)).sqrt() }
-// 눈의 랜드마크 6개를 받아 눈의 개방 비율(EAR)을 계산하여 졸음을 판단합니다.
-fn get_ear(eye_landmarks: &[Landmark]) -> f64 { let ver_dist1 = get_distance(&eye_landmarks[1], &eye_landmarks[5]); let ver_dist2 = get_distance(&eye_landmarks[2], &eye_landmarks[4]); let hor_dist = get_distance(&eye_landmarks[0], &eye_landmarks[3]); if hor_dist == 0.0 { return 0.0; } (ver_dist1 + ver_dist2) / (2.0 * hor_dist) }
-// 입의 랜드마크 8개를 받아 입의 개방 비율(MAR)을 계산하여 하품을 판단합니다.
-fn get_mar(mouth_landmarks: &[Landmark]) -> f64 { let ver_dist1 = get_distance(&mouth_landmarks[2], &mouth_landmarks[5]); let ver_dist2 = get_distance(&mouth_landmarks[3], &mouth_landmarks[6]); let ver_dist3 = get_distance(&mouth_landmarks[4], &mouth_landmarks[7]); let hor_dist = get_distance(&mouth_landmarks[0], &mouth_landmarks[1]); if hor_dist == 0.0 { return 0.0; } (ver_dist1 + ver_dist2 + ver_dist3) / (3.0 * hor_dist) }
-// 코와 양 볼의 랜드마크를 이용해 고개의 좌우 회전(Yaw) 정도를 추정하여 주의 분산을 판단합니다.
-fn get_head_yaw(landmarks_map: &HashMap<u32, Landmark>) -> f64 { if let (Some(&nose), Some(&left_cheek), Some(&right_cheek)) = (landmarks_map.get(&1), landmarks_map.get(&234), landmarks_map.get(&454)) { let dist_left = (nose.x - left_cheek.x).abs(); let dist_right = (right_cheek.x - nose.x).abs(); if (dist_left + dist_right) == 0.0 { return 0.0; } (dist_right - dist_left) / (dist_left + dist_right) } else { 0.0 } }
+// SIGHUP으로 다시 읽어 실행 중인 모든 연결에 적용하는 설정입니다. (서버 설정과, 그 설정으로 불러온 알람 문구)
+struct LiveSettings {
+    config: ServerConfig,
+    alarm_catalog: AlarmCatalog,
+}
 
 
 // --- 프로그램의 시작점, main 함수 ---
 #[tokio::main] // Tokio 비동기 런타임을 활성화하는 매크로입니다.
 async fn main() {
-    // 1. 환경 변수에서 Redis 접속 정보를 읽어옵니다. (없으면 기본값 "127.0.0.1", "6379" 사용)
-    let redis_host = env::var("REDIS_HOST").unwrap_or_else(|_| "127.0.0.1".to_string());
-    let redis_port = env::var("REDIS_PORT").unwrap_or_else(|_| "6379".to_string());
-    let redis_url = format!("redis://{}:{}", redis_host, redis_port);
-    let redis_client = match redis::Client::open(redis_url) { Ok(client) => client, Err(e) => { eprintln!("🔴 Redis client creation failed: {:?}", e); return; } };
-
-    // 2. 웹소켓 서버가 사용할 주소(0.0.0.0: 모든 네트워크 인터페이스)와 포트(9001)를 설정하고, TCP 리스너를 바인딩합니다.
-    let addr = "0.0.0.0:9001";
-    let listener = match TcpListener::bind(&addr).await { Ok(listener) => listener, Err(e) => { eprintln!("🔴 TCP listener bind failed: {:?}", e); return; } };
-    println!("🚀 WebSocket server starting...");
-
-    // 3. 시스템 종료 신호(Ctrl+C)와 재시작 신호(SIGHUP)를 처리할 핸들러를 설정합니다.
+    // 1. 서버 설정을 읽고 검증합니다. (CONFIG_PATH 또는 config.toml + 환경 변수) 잘못된 설정이면 서버를 시작하지 않습니다.
+    let config = match ServerConfig::load() { Ok(config) => config, Err(e) => { eprintln!("🔴 설정 오류:\n{}", e); process::exit(1); } };
+    if config.delivery.sinks.contains(&SinkKind::Stdout) { logging::use_stderr(); } // 표준 출력은 이벤트 전용으로 씁니다.
+    // 모든 연결이 함께 쓰는 Redis 연결 하나를 백그라운드에서 만듭니다. (멀티플렉싱) 연결이 끊기면 다음 명령 때 자동으로 다시 연결합니다.
+    // Redis가 아직 내려가 있어도 서버는 먼저 시작하고, 연결될 때까지 이벤트는 발송 대기열에 보관합니다.
+    // redis 출력 대상을 쓰지 않으면 Redis 없이 실행합니다. (로컬 개발 등)
+    let redis = SharedRedis::default();
+    if config.delivery.uses_redis() {
+        let redis_client = match redis::Client::open(config.redis.url()) { Ok(client) => client, Err(e) => { eprintln!("🔴 Redis client creation failed: {:?}", e); process::exit(1); } };
+        tokio::spawn(connect_redis(redis.clone(), redis_client, config.redis.clone()));
+    } else {
+        log!("🟡 Redis 없이 실행합니다. 보정 결과는 저장되지 않습니다.");
+    }
+    // 세션 이벤트는 발송 대기열을 거쳐 설정된 출력 대상들에 보냅니다. 출력 대상이 잠시 실패해도 이벤트를 잃지 않고 나중에 순서대로 보냅니다.
+    let sinks = match sinks::build_sinks(&config.delivery, &redis) { Ok(sinks) => sinks, Err(e) => { eprintln!("🔴 출력 대상 설정 오류: {}", e); process::exit(1); } };
+    log!("📤 이벤트 출력 대상: {}", sinks.iter().map(|sink| sink.name()).collect::<Vec<_>>().join(", "));
+    let (outbox, outbox_task) = Outbox::start(sinks, config.delivery.clone());
+
+    // 핸드셰이크 토큰 인증 설정을 읽습니다. 서명 키가 없으면 (auth.disabled가 아닌 한) 서버를 시작하지 않습니다.
+    let authenticator = match Authenticator::from_config(&config.auth) {
+        Ok(Some(authenticator)) => { log!("🔐 토큰 인증이 활성화되었습니다."); Some(Arc::new(authenticator)) },
+        Ok(None) => { log!("🟡 AUTH_DISABLED: 토큰 인증 없이 연결을 받습니다."); None },
+        Err(e) => { eprintln!("🔴 인증 설정 오류: {}", e); process::exit(1); },
+    };
+
+    // 연결이 끊긴 세션을 재접속에 대비해 보관할 저장소입니다. session.resume_ttl_secs(기본 120초) 안에 돌아오지 않은 세션은 만료 처리합니다.
+    let session_store = Arc::new(SessionStore::new(Duration::from_secs(config.session.resume_ttl_secs)));
+
+    // 2. 설정된 주소(기본 0.0.0.0:9001)로 TCP 리스너를 바인딩합니다.
+    let addr = config.listen_addr();
+    let listener = match TcpListener::bind(&addr).await { Ok(listener) => listener, Err(e) => { eprintln!("🔴 TCP listener bind failed: {:?}", e); process::exit(1); } };
+    log!("🚀 WebSocket server starting...");
+
+    // 분석 임계값, 알람 정책, 알람 문구처럼 실행 중에 바꿀 수 있는 설정은 watch 채널로 모든 연결에 나눠줍니다.
+    // 알람 문구 카탈로그는 alarms.catalog_dir이 지정되면 그 디렉터리의 <언어>.json 파일로 기본 문구를 덮어씁니다.
+    let alarm_catalog = AlarmCatalog::load(config.alarms.catalog_dir.as_deref());
+    let (settings, _) = watch::channel(Arc::new(LiveSettings { config, alarm_catalog }));
+    tokio::spawn(expire_parked_sessions(session_store.clone(), outbox.clone(), settings.subscribe()));
+
+    // 3. 시스템 종료 신호(Ctrl+C, SIGTERM)와 설정 리로드 신호(SIGHUP)를 처리할 핸들러를 설정합니다. (쿠버네티스는 파드를 내릴 때 SIGTERM을 보냅니다.)
     let mut hup = signal(SignalKind::hangup()).expect("Failed to install SIGHUP handler");
+    let mut term = signal(SignalKind::terminate()).expect("Failed to install SIGTERM handler");
+    // 종료가 시작되었음을 모든 연결에 알리는 채널과, 종료 시 끝나기를 기다릴 연결 작업들입니다.
+    let (shutdown, _) = watch::channel(false);
+    let mut connections = JoinSet::new();
 
     // 4. 메인 루프: 새로운 클라이언트 접속 및 시스템 신호를 비동기적으로 동시에 기다립니다.
     loop {
@@ -91,213 +116,567 @@ async fn main() {
             // 새 클라이언트가 접속하면...
             result = listener.accept() => {
                 if let Ok((stream, _)) = result {
-                    // Redis 클라이언트를 복제하여 새 클라이언트 처리 작업에 넘겨줍니다. (소유권 문제 방지)
-                    let client_clone = redis_client.clone();
                     // 각 클라이언트를 독립적인 비동기 작업(일종의 경량 스레드)으로 생성하여 동시에 처리합니다. (Rust 동시성의 핵심)
-                    tokio::spawn(handle_connection(stream, client_clone));
+                    // 공유 Redis 연결은 핸들을 복제하여 넘겨줍니다. (복제해도 실제 연결은 하나입니다.)
+                    connections.spawn(handle_connection(stream, redis.clone(), outbox.clone(), settings.subscribe(), shutdown.subscribe(), authenticator.clone(), session_store.clone()));
                 }
             },
-            // Ctrl+C 신호를 받으면...
+            // 끝난 연결 작업은 그때그때 정리합니다.
+            Some(_) = connections.join_next(), if !connections.is_empty() => {},
+            // Ctrl+C 또는 SIGTERM 신호를 받으면...
             _ = signal::ctrl_c() => {
-                println!("\nℹ️ Ctrl+C received, shutting down.");
-                break; // 루프를 종료하여 프로그램을 안전하게 끝냅니다.
+                log!("\nℹ️ Ctrl+C received, shutting down.");
+                break; // 루프를 종료하고, 아래에서 진행 중인 세션을 정리한 뒤 프로그램을 끝냅니다.
+            },
+            _ = term.recv() => {
+                log!("ℹ️ SIGTERM received, shutting down.");
+                break;
             },
-            // SIGHUP 신호를 받으면... (보통 설정 리로드 등에 쓰이지만 여기선 무시)
+            // SIGHUP 신호를 받으면 설정을 다시 읽어 모든 연결에 적용합니다. (연결은 끊지 않습니다.)
             _ = hup.recv() => {
-                println!("🟡 SIGHUP received, ignoring.");
+                log!("🟡 SIGHUP received, reloading configuration.");
+                reload_settings(&settings);
             }
         }
     }
-}
 
-// 클라이언트의 집중도 상태를 명확하게 관리하기 위한 '상태 머신(State Machine)'입니다.
-#[derive(PartialEq, Debug, Clone, Copy)]
-enum AttentionState {
-    Focused,      // 집중 상태
-    Drowsy,       // 졸음 상태
-    Distracted,   // 주의 분산 상태
-    UserLeft,     // 자리 비움 상태
-    Paused,       // 사용자가 직접 일시정지한 상태
+    // 5. 정상 종료: 새 접속을 더 받지 않고, 모든 연결에 종료를 알린 뒤 각 연결이 세션을 정리하고 끝나기를 기다립니다.
+    // 연결 정리와 발송 대기열 비우기가 함께 정해진 시간(listener.shutdown_timeout_secs) 안에 끝나야 합니다. (쿠버네티스의 SIGKILL 전에)
+    // 그중 1/4은 발송 대기열에 남겨두고, 그 전까지 끝나지 않은 연결은 강제로 종료합니다.
+    drop(listener);
+    shutdown.send_replace(true);
+    let timeout = Duration::from_secs(settings.borrow().config.listener.shutdown_timeout_secs);
+    let deadline = tokio::time::Instant::now() + timeout;
+    log!("⏳ 연결 {}개의 세션을 정리하는 중입니다... (최대 {}초)", connections.len(), timeout.as_secs());
+    let drained = tokio::time::timeout_at(deadline - timeout / 4, async { while connections.join_next().await.is_some() {} }).await;
+    if drained.is_err() {
+        eprintln!("🔴 종료 대기 시간이 지나 남은 연결 {}개를 강제로 종료합니다.", connections.len());
+        connections.shutdown().await;
+    }
+    // 재접속을 기다리며 보관 중이던 세션도, 서버가 내려가면 돌아올 곳이 없으므로 함께 정리합니다.
+    suspend_parked_sessions(&session_store, &outbox, &settings.borrow().config.channels.meaningful_events);
+    // 대기열에 남은 이벤트를 마저 발행합니다. 발행하지 못한 이벤트는 디스크에 남겨 다음 실행에서 이어서 발행합니다.
+    outbox.close();
+    if tokio::time::timeout_at(deadline, outbox_task).await.is_err() { eprintln!("🔴 종료 대기 시간이 지나 발송 대기열을 마저 비우지 못했습니다."); }
+    log!("👋 서버를 종료합니다.");
 }
 
 // --- 개별 클라이언트 연결을 처리하는 핵심 함수 ---
-async fn handle_connection(stream: TcpStream, redis_client: redis::Client) {
-    // 1. 초기 설정: 클라이언트 주소 확인, Redis 연결, 웹소켓 핸드셰이크(HTTP 연결을 웹소켓 연결로 업그레이드)를 수행합니다.
+async fn handle_connection(stream: TcpStream, redis: SharedRedis, outbox: Outbox, mut settings: watch::Receiver<Arc<LiveSettings>>, mut shutdown: watch::Receiver<bool>, authenticator: Option<Arc<Authenticator>>, session_store: Arc<SessionStore>) {
+    // 1. 초기 설정: 클라이언트 주소 확인, 웹소켓 핸드셰이크(HTTP 연결을 웹소켓 연결로 업그레이드)를 수행합니다.
+    // Redis는 모든 연결이 공유하는 연결을 쓰므로, Redis가 잠시 내려가 있어도 클라이언트 연결은 그대로 받습니다.
     let addr = match stream.peer_addr() { Ok(addr) => addr, Err(_) => return };
-    let mut redis_conn = match redis_client.get_async_connection().await { Ok(conn) => conn, Err(_) => return };
-    let ws_stream = match accept_async(stream).await {
+    // 핸드셰이크 요청의 Accept-Language 헤더를 기록해 두었다가 알람 언어를 정하는 데 사용합니다.
+    // 인증이 켜져 있으면 요청의 토큰을 검증하고, 토큰이 없거나 잘못되었으면 웹소켓으로 업그레이드하지 않고 401로 거부합니다.
+    let mut accept_language: Option<String> = None;
+    let mut authenticated: Option<AuthenticatedUser> = None;
+    #[allow(clippy::result_large_err)] // 콜백의 반환 타입(ErrorResponse)은 tungstenite가 정한 것입니다.
+    let check_handshake = |request: &Request, mut response: Response| {
+        accept_language = request.headers().get("accept-language").and_then(|value| value.to_str().ok()).map(str::to_string);
+        let Some(authenticator) = &authenticator else { return Ok(response) };
+        let verified = token_from_request(request).ok_or_else(|| "missing token".to_string())
+            .and_then(|(token, subprotocol)| authenticator.verify(&token).map(|user| (user, subprotocol)));
+        match verified {
+            Ok((user, subprotocol)) => {
+                // 토큰을 서브프로토콜 헤더로 받았다면, 브라우저가 연결을 수락하도록 선택한 서브프로토콜을 응답에 담아야 합니다.
+                if let Some(subprotocol) = subprotocol { response.headers_mut().insert("sec-websocket-protocol", HeaderValue::from_static(subprotocol)); }
+                authenticated = Some(user);
+                Ok(response)
+            },
+            Err(e) => {
+                log!("🔒 인증 실패로 연결을 거부합니다 ({}): {}", addr, e);
+                let mut rejection = ErrorResponse::new(Some("Unauthorized".to_string()));
+                *rejection.status_mut() = StatusCode::UNAUTHORIZED;
+                Err(rejection)
+            },
+        }
+    };
+    let ws_stream = match accept_hdr_async(stream, check_handshake).await {
         Ok(ws) => ws,
         Err(e) => {
-            // AWS 로드밸런서가 주기적으로 보내는 헬스 체크 요청은 정상 처리하고, 실제 에러만 로그에 남깁니다.
-            if let tokio_tungstenite::tungstenite::Error::Protocol(tokio_tungstenite::tungstenite::error::ProtocolError::MissingConnectionUpgradeHeader) = e {
-                println!("ℹ️  ALB Health Check received (normal behavior)");
-            } else {
-                eprintln!("🔴 WebSocket handshake error ({}): {:?}", addr, e);
+            // AWS 로드밸런서가 주기적으로 보내는 헬스 체크 요청은 정상 처리하고, 실제 에러만 로그에 남깁니다. (인증 실패는 위에서 이미 기록)
+            match e {
+                tokio_tungstenite::tungstenite::Error::Protocol(tokio_tungstenite::tungstenite::error::ProtocolError::MissingConnectionUpgradeHeader) => log!("ℹ️  ALB Health Check received (normal behavior)"),
+                tokio_tungstenite::tungstenite::Error::Http(_) => {},
+                e => eprintln!("🔴 WebSocket handshake error ({}): {:?}", addr, e),
             }
             return;
         }
     };
-    println!("🚀 WebSocket connection established: {}", addr);
+    log!("🚀 WebSocket connection established: {}", addr);
 
     // 2. 웹소켓 스트림을 '쓰기 전용(write)'과 '읽기 전용(read)'으로 분리하고, 각종 상태 변수들을 초기화합니다.
     let (mut write, mut read) = ws_stream.split();
-    let mut ping_interval = interval(Duration::from_secs(30)); // 30초마다 연결 유지를 위한 Ping 메시지를 보내도록 타이머 설정
-
-    let mut current_state = AttentionState::Focused; // 현재 집중도 상태의 초기값은 '집중'으로 설정합니다.
-    let mut state_changed_at = Instant::now(); // 상태가 마지막으로 변경된 시각을 기록합니다.
-    let mut yawn_count: u32 = 0; // 하품 횟수를 세기 위한 카운터입니다.
-
-    // 3. 분석에 사용할 각종 임계값(Threshold)을 상수로 정의합니다.
-    const EAR_THRESHOLD: f64 = 0.21;     // 이 값보다 EAR이 작으면 '졸음'으로 판단합니다.
-    const MAR_THRESHOLD: f64 = 0.6;      // 이 값보다 MAR이 크면 '하품'으로 판단합니다.
-    const YAW_THRESHOLD: f64 = 0.3;      // 이 값보다 고개 회전이 크면 '주의 분산'으로 판단합니다.
-
-    // 4. 클라이언트와의 모든 상호작용을 처리하는 메인 이벤트 루프입니다.
+    let mut live = settings.borrow_and_update().clone(); // 이 연결에 적용 중인 설정입니다. SIGHUP으로 설정이 바뀌면 새 설정으로 교체됩니다.
+    let mut ping_interval = interval(Duration::from_secs(live.config.listener.ping_interval_secs)); // 주기적으로(기본 30초) 연결 유지를 위한 Ping 메시지를 보내도록 타이머 설정
+    let mut inactivity_check = interval(Duration::from_secs(1)); // 1초마다 프레임이 끊겼는지(비활성) 확인하도록 타이머 설정
+
+    let mut engine = AttentionEngine::new(live.config.analysis.engine_config(), now_ms()); // 이 연결의 집중도 상태 머신을 담고 있는 분석 엔진입니다.
+    let mut session: Option<SessionIdentity> = None; // 첫 'start' 메시지로 이 연결에 묶인 세션/사용자입니다. 이후 다른 세션/사용자의 메시지는 거부합니다.
+    let mut session_ended = false; // 'end' 이벤트로 세션이 정상 종료되었는지 여부입니다.
+    let mut disconnect_reason = "closed"; // 'end' 없이 연결이 끝났을 때 CONNECTION_LOST에 기록할 사유입니다.
+    let mut last_alarms: HashMap<AlarmCode, u64> = HashMap::new(); // 알람 종류별 마지막 전송 시각입니다. (알람 쿨다운)
+    let connection_id = session_store.connection_id(); // 세션 보관소에서 이 연결을 구분하는 번호입니다.
+    let (takeover_tx, mut takeover_rx) = mpsc::channel::<Handover>(1); // 같은 세션으로 다시 접속한 연결이 이 연결의 세션을 넘겨달라고 요청하는 채널입니다.
+    let mut handed_over = false; // 세션을 다른 연결에 넘겨주고 끝났는지 여부입니다.
+    let mut negotiated: Option<Negotiated> = None; // 'hello' 또는 'start'로 합의된 프로토콜 버전과 기능입니다. 합의 전에는 다른 이벤트를 받지 않습니다.
+    // 알람 언어입니다. 'start' 이벤트의 language 값이 우선이고, 없으면 Accept-Language 헤더, 그것도 없으면 한국어를 사용합니다.
+    let mut language = accept_language.as_deref().and_then(|header| live.alarm_catalog.negotiate(header)).unwrap_or_else(|| FALLBACK_LANGUAGE.to_string());
+
+    // 3. 클라이언트와의 모든 상호작용을 처리하는 메인 이벤트 루프입니다.
+    // 각 분기는 분석 엔진이 돌려준 이벤트들을 내놓고, 그 이벤트들은 루프 아래쪽에서 한꺼번에 발행/전송합니다.
     loop {
-        tokio::select! {
+        let events = tokio::select! {
             // 클라이언트로부터 메시지가 오기를 비동기적으로 기다립니다.
             msg_result = read.next() => {
-                let msg = match msg_result { Some(Ok(m)) => m, _ => break }; // 메시지가 없거나 에러가 발생하면 연결을 종료합니다.
-
-                // 텍스트 형식의 메시지만 처리합니다.
-                if let Message::Text(text) = msg {
-                    // 받은 텍스트(JSON)를 ClientMessage 구조체로 안전하게 파싱합니다.
-                    if let Ok(client_msg) = serde_json::from_str::<ClientMessage>(&text) {
-
-                        // 만약 '일시정지' 상태에서 'data' 이벤트가 오면, 분석은 건너뛰고 데이터만 Redis에 기록합니다.
-                        if current_state == AttentionState::Paused && client_msg.event_type == "data" {
-                            let _ = redis_conn.publish::<_, _, i64>("attention-events", &text).await;
-                            continue; // 다음 루프로 넘어갑니다.
+                // 메시지가 없거나(클라이언트가 연결을 닫음) 에러가 발생하면 연결을 종료합니다.
+                let msg = match msg_result { Some(Ok(m)) => m, Some(Err(_)) => { disconnect_reason = "error"; break }, None => break };
+
+                // 텍스트(JSON) 메시지는 메시지 봉투로, 다시 이벤트 종류별 구조체로 검증합니다. 잘못된 메시지는 클라이언트에게 에러로 알려줍니다.
+                // 바이너리 프레임은 협상한 랜드마크 순서에 따라 'data' 이벤트로 변환합니다. (세션/사용자 정보는 앞서 받은 텍스트 메시지의 것을 사용)
+                let (client_msg, client_event) = match msg {
+                    Message::Text(text) => {
+                        let mut client_msg = match ClientMessage::parse(&text) {
+                            Ok(client_msg) => client_msg,
+                            Err(e) => { send_message(&mut write, &error_message(e, None)).await; continue; },
+                        };
+                        // 인증된 연결에서는 메시지의 userId 대신 토큰의 사용자 ID를 사용합니다.
+                        if let Err(e) = bind_user(&mut client_msg, authenticated.as_ref()) { send_message(&mut write, &error_message(e, Some(&client_msg))).await; continue; }
+                        match ClientEvent::parse(&client_msg) {
+                            Ok(client_event) => (client_msg, client_event),
+                            Err(e) => { send_message(&mut write, &error_message(e, Some(&client_msg))).await; continue; },
                         }
+                    },
+                    Message::Binary(bytes) => {
+                        let Some(landmark_indices) = negotiated.as_ref().map(|n| &n.landmark_indices).filter(|indices| !indices.is_empty()) else {
+                            send_message(&mut write, &error_message(ProtocolError::new("BINARY_FRAMES_NOT_NEGOTIATED", "negotiate 'binaryFrames' with landmarkIndices in 'hello' or 'start' first"), None)).await;
+                            continue;
+                        };
+                        // 바이너리 프레임에는 세션/사용자 정보가 없으므로 연결에 묶인 세션의 것을 사용합니다. (세션 시작 전이면 아래에서 거부됩니다.)
+                        let identity = session.clone().unwrap_or(SessionIdentity { session_id: String::new(), user_id: String::new() });
+                        let client_msg = ClientMessage { session_id: identity.session_id, user_id: identity.user_id, event_type: "data".to_string(), payload: Value::Null };
+                        match decode_landmark_frame(&bytes, landmark_indices) {
+                            Ok(data_payload) => (client_msg, ClientEvent::Data(data_payload)),
+                            Err(e) => { send_message(&mut write, &error_message(e, Some(&client_msg))).await; continue; },
+                        }
+                    },
+                    _ => continue, // Ping/Pong/Close 프레임은 웹소켓 라이브러리가 처리합니다.
+                };
+                // 프로토콜 버전 협상('hello' 또는 'start') 전에는 다른 이벤트를 받지 않습니다.
+                if negotiated.is_none() && !matches!(client_event, ClientEvent::Hello(_) | ClientEvent::Start(_)) {
+                    send_message(&mut write, &error_message(ProtocolError::new("HANDSHAKE_REQUIRED", "send 'hello' or 'start' with a protocolVersion first"), Some(&client_msg))).await;
+                    continue;
+                }
+                // 'start' 전의 세션 이벤트나, 연결에 묶인 것과 다른 세션/사용자의 메시지는 거부합니다.
+                if let Err(e) = check_session(session.as_ref(), &client_msg, &client_event) {
+                    send_message(&mut write, &error_message(e, Some(&client_msg))).await;
+                    continue;
+                }
+                let identity = session.clone().unwrap_or_else(|| SessionIdentity::of(&client_msg)); // 'start'라면 이번 메시지로 새로 묶을 세션입니다.
+
+                // 만약 '일시정지' 상태에서 'data' 이벤트가 오면, 분석은 건너뛰고 데이터만 출력 대상에 기록합니다. (실패하면 보관하지 않고 버립니다.)
+                // 텍스트/바이너리 어느 쪽으로 받았든, 연결에 묶인 세션/사용자로 같은 JSON 형식을 만들어 기록합니다.
+                if let (AttentionState::Paused, ClientEvent::Data(data_payload)) = (engine.state(), &client_event) {
+                    let raw_event = json!({ "sessionId": identity.session_id, "userId": identity.user_id, "eventType": "data", "payload": data_payload });
+                    outbox.publish_best_effort(&live.config.channels.raw_events, "data", raw_event.to_string());
+                    continue; // 다음 루프로 넘어갑니다.
+                }
 
-                        let mut new_state = current_state;
-
-                        // 이벤트 타입에 따라 다른 로직을 수행합니다.
-                        match client_msg.event_type.as_str() {
-                            "data" => { // 핵심: 집중도 분석 로직
-                                // payload의 랜드마크 데이터를 파싱합니다.
-                                if let Ok(data_payload) = serde_json::from_value::<DataPayload>(client_msg.payload.clone()) {
-                                    // 랜드마크 데이터를 인덱스로 빠르게 찾기 위해 해시맵으로 변환합니다.
-                                    let landmarks_map: HashMap<u32, Landmark> =
-                                        data_payload.landmarks.iter().map(|&lm| (lm.index, lm)).collect();
-
-                                    // EAR, MAR, Head Yaw 등 주요 특징 값을 계산합니다.
-                                    let ear_left = get_ear(&get_landmarks_by_indices(&landmarks_map, &[362, 385, 387, 263, 373, 380]));
-                                    let ear_right = get_ear(&get_landmarks_by_indices(&landmarks_map, &[33, 160, 158, 133, 153, 144]));
-                                    let mar = get_mar(&get_landmarks_by_indices(&landmarks_map, &[61, 291, 13, 81, 178, 14, 311, 402]));
-                                    let head_yaw = get_head_yaw(&landmarks_map);
-
-                                    // 계산된 값을 바탕으로 사용자의 새로운 상태를 결정합니다.
-                                    new_state = if ear_left < EAR_THRESHOLD && ear_right < EAR_THRESHOLD {
-                                        AttentionState::Drowsy
-                                    } else if head_yaw.abs() > YAW_THRESHOLD {
-                                        AttentionState::Distracted
-                                    } else {
-                                        AttentionState::Focused
-                                    };
-
-                                    // 하품을 감지하면 Redis에 이벤트를 발행하고, 5회마다 클라이언트에게 알람을 보냅니다.
-                                    if mar > MAR_THRESHOLD {
-                                        create_and_publish_event(&mut redis_conn, &client_msg, "YAWN_DETECTED", json!({})).await;
-                                        yawn_count += 1;
-                                        if yawn_count > 0 && yawn_count % 5 == 0 {
-                                            let yawn_alarm = format!("하품 {}회 감지! 스트레칭 한번 어떠세요? 🤸", yawn_count);
-                                            send_alarm(&mut write, &yawn_alarm).await;
-                                        }
-                                    }
-                                }
-                            },
-                            "status_update" => { // 얼굴 미감지, 일시정지 등 클라이언트의 상태 변경을 처리합니다.
-                                if let Ok(status_payload) = serde_json::from_value::<StatusPayload>(client_msg.payload.clone()) {
-                                    match status_payload.status.as_str() {
-                                        "no_face_detected" => new_state = AttentionState::UserLeft,
-                                        "paused" => new_state = AttentionState::Paused,
-                                        "resumed" => new_state = AttentionState::Focused,
-                                        _ => {} // 그 외의 상태는 무시합니다.
-                                    }
-                                }
+                // 이벤트 종류에 따라 분석 엔진에 입력을 넘기고, 그 결과 이벤트들을 받아옵니다.
+                match client_event {
+                    ClientEvent::Hello(hello) => { // 프로토콜 버전과 기능을 협상합니다. 지원하지 않는 버전이면 연결을 닫습니다.
+                        match negotiate(Some(hello.protocol_version), hello.capabilities.as_deref(), hello.landmark_indices.as_deref()) {
+                            Ok(result) => { send_message(&mut write, &welcome_message(&result)).await; negotiated = Some(result); continue; },
+                            Err(e) => { send_message(&mut write, &error_message(e, Some(&client_msg))).await; disconnect_reason = "protocol_error"; break; },
+                        }
+                    },
+                    ClientEvent::Data(data_payload) => { // 핵심: 집중도 분석 로직
+                        engine.process_frame(&data_payload.landmarks, now_ms())
+                    },
+                    ClientEvent::StatusUpdate(status_payload) => { // 얼굴 미감지, 일시정지 등 클라이언트의 상태 변경을 처리합니다.
+                        match ClientStatus::parse(&status_payload.status) {
+                            Some(status) => engine.process_status(status, now_ms()),
+                            None => {
+                                send_message(&mut write, &error_message(ProtocolError::new("INVALID_PAYLOAD", format!("unknown status '{}'", status_payload.status)), Some(&client_msg))).await;
+                                continue;
                             },
-                            "start" => { create_and_publish_event(&mut redis_conn, &client_msg, "SESSION_START", client_msg.payload.clone()).await; continue; },
-                            "end" => { create_and_publish_event(&mut redis_conn, &client_msg, "SESSION_END", client_msg.payload.clone()).await; break; },
-                            _ => {} // 정의되지 않은 이벤트 타입은 무시합니다.
                         }
-
-                        // 상태가 실제로 변경되었는지 확인하여, 불필요한 이벤트 발행을 막습니다.
-                        if new_state != current_state {
-                            let duration_ms = state_changed_at.elapsed().as_millis(); // 이전 상태가 지속된 시간을 계산합니다.
-
-                            // 이전 상태와 새 상태를 기반으로 "DROWSINESS_STARTED" 등 의미 있는 이벤트 타입을 결정합니다.
-                            let event_type = match (current_state, new_state) {
-                                (AttentionState::Paused, AttentionState::Focused) => "SESSION_RESUMED",
-                                (_, AttentionState::Focused) => "FOCUS_RESTORED",
-                                (_, AttentionState::Paused) => "SESSION_PAUSED",
-                                (_, AttentionState::Drowsy) => "DROWSINESS_STARTED",
-                                (_, AttentionState::Distracted) => "DISTRACTION_STARTED",
-                                (_, AttentionState::UserLeft) => "USER_LEFT",
-                            };
-
-                            // 결정된 상태 변경 이벤트를 생성하여 Redis에 발행합니다.
-                            create_and_publish_event(&mut redis_conn, &client_msg, event_type, json!({ "previousStateDurationMs": duration_ms })).await;
-
-                            // 현재 상태를 새로운 상태로 업데이트하고, 상태 변경 시각을 지금으로 재설정합니다.
-                            current_state = new_state;
-                            state_changed_at = Instant::now();
-
-                            // 새로운 상태에 맞는 알람 메시지를 생성하여 클라이언트에게 실시간으로 전송합니다.
-                            let alarm_msg = match new_state {
-                                AttentionState::Drowsy => "졸음이 감지되었습니다! 잠시 쉬어가는 건 어떨까요? ☕",
-                                AttentionState::Distracted => "주의가 분산되었습니다! 다시 집중해볼까요? 💪",
-                                AttentionState::UserLeft => "사용자가 자리를 비웠나요? 얼굴이 감지되지 않습니다. 🤔",
-                                _ => "" // 알람을 보낼 필요 없는 상태
-                            };
-                            if !alarm_msg.is_empty() { send_alarm(&mut write, alarm_msg).await; }
+                    },
+                    ClientEvent::Start(start_payload) => {
+                        // 'hello' 없이 바로 시작하는 클라이언트는 여기서 프로토콜 버전을 협상합니다.
+                        if negotiated.is_none() {
+                            match negotiate(start_payload.protocol_version, start_payload.capabilities.as_deref(), start_payload.landmark_indices.as_deref()) {
+                                Ok(result) => { send_message(&mut write, &welcome_message(&result)).await; negotiated = Some(result); },
+                                Err(e) => { send_message(&mut write, &error_message(e, Some(&client_msg))).await; disconnect_reason = "protocol_error"; break; },
+                            }
                         }
-                    }
+                        if let Some(requested) = start_payload.language.as_deref().and_then(|l| live.alarm_catalog.resolve(l)) { language = requested; }
+                        let resumed = match claim_session(&session_store, &identity, connection_id, takeover_tx.clone()).await {
+                            Ok(resumed) => resumed,
+                            Err(e) => { send_message(&mut write, &error_message(e, Some(&client_msg))).await; continue; },
+                        };
+                        session = Some(identity.clone());
+                        if let Some((previous, disconnected_ms)) = resumed {
+                            resume_session(&mut engine, previous, disconnected_ms, &live, &outbox, &identity, &client_msg);
+                            send_message(&mut write, &ack_message(&client_msg)).await;
+                            if negotiated.as_ref().is_some_and(|n| n.has(CAP_STATE)) { send_message(&mut write, &state_message(engine.state())).await; }
+                            continue;
+                        }
+                        log!("🔗 세션이 연결에 묶였습니다: sessionId={}, userId={}", identity.session_id, identity.user_id);
+                        create_and_publish_event(&outbox, &live.config.channels.meaningful_events, &identity, "SESSION_START", client_msg.payload.clone());
+                        send_message(&mut write, &ack_message(&client_msg)).await;
+                        restore_calibration(&mut engine, &redis, &live, &outbox, &identity).await
+                    },
+                    ClientEvent::Calibrate(_) => { // 클라이언트가 명시적으로 재보정을 요청한 경우
+                        send_message(&mut write, &ack_message(&client_msg)).await;
+                        engine.start_calibration(now_ms())
+                    },
+                    ClientEvent::End(end_payload) => {
+                        log!("🏁 세션 종료 요청 (사유: {})", end_payload.reason.as_deref().unwrap_or("unknown"));
+                        // 세션 종료 전에, 아직 열려 있는 현재 상태의 지속 시간을 먼저 정산합니다.
+                        for event in engine.finish(now_ms()) { create_and_publish_event(&outbox, &live.config.channels.meaningful_events, &identity, event.event_type(), event.payload()); }
+                        create_and_publish_event(&outbox, &live.config.channels.meaningful_events, &identity, "SESSION_END", client_msg.payload.clone());
+                        send_message(&mut write, &ack_message(&client_msg)).await;
+                        session_ended = true;
+                        break;
+                    },
                 }
             },
-            // 30초마다 Ping 메시지를 보내 연결이 끊겼는지 확인하고, 연결 유지를 돕습니다.
+            // 주기적으로 Ping 메시지를 보내 연결이 끊겼는지 확인하고, 연결 유지를 돕습니다.
             _ = ping_interval.tick() => {
-                if write.send(Message::Ping(vec![])).await.is_err() { break; } // Ping 전송 실패 시 연결 끊김으로 간주하고 루프 종료
+                if write.send(Message::Ping(vec![])).await.is_err() { disconnect_reason = "ping_failed"; break; } // Ping 전송 실패 시 연결 끊김으로 간주하고 루프 종료
+                continue;
+            },
+            // 설정이 다시 로드되면, 진행 중인 세션은 그대로 두고 분석 임계값/알람 정책/Ping 주기만 새 설정으로 바꿉니다.
+            Ok(()) = settings.changed() => {
+                let previous = std::mem::replace(&mut live, settings.borrow_and_update().clone());
+                engine.reconfigure(live.config.analysis.engine_config());
+                if previous.config.listener.ping_interval_secs != live.config.listener.ping_interval_secs {
+                    ping_interval = interval(Duration::from_secs(live.config.listener.ping_interval_secs));
+                }
+                continue;
+            },
+            // 같은 세션으로 다시 접속한 연결이 세션을 넘겨달라고 하면, 엔진을 넘겨주고 이 연결을 닫습니다. (세션은 새 연결에서 이어지므로 끊김 이벤트는 발행하지 않습니다.)
+            Some(handover) = takeover_rx.recv() => {
+                let fresh = AttentionEngine::new(live.config.analysis.engine_config(), now_ms());
+                let _ = handover.send(std::mem::replace(&mut engine, fresh));
+                log!("🔁 세션을 새 연결에 넘겨주었습니다: sessionId={}", session.as_ref().map_or("", |s| s.session_id.as_str()));
+                let close = CloseFrame { code: CloseCode::Normal, reason: "session resumed on another connection".into() };
+                let _ = write.send(Message::Close(Some(close))).await;
+                handed_over = true;
+                break;
+            },
+            // 서버가 종료되면 클라이언트에게 재시작을 알리는 Close 프레임을 보내고 연결을 끝냅니다. (클라이언트는 잠시 뒤 재접속할 수 있습니다.)
+            Ok(()) = shutdown.changed() => {
+                let close = CloseFrame { code: CloseCode::Restart, reason: "server restarting".into() };
+                let _ = write.send(Message::Close(Some(close))).await;
+                disconnect_reason = "server_shutdown";
+                break;
+            },
+            // 세션이 시작된 뒤에는 프레임이 끊겼는지 주기적으로 확인합니다. (탭이 백그라운드로 가면 브라우저가 분석을 멈춥니다.)
+            _ = inactivity_check.tick(), if session.is_some() => engine.check_inactivity(now_ms()),
+        };
+        let Some(identity) = session.clone() else { continue };
+        // 엔진이 돌려준 이벤트를 Redis에 발행하고, 필요한 경우 클라이언트에게 상태/알람/점수 메시지를 보냅니다.
+        for event in events {
+            create_and_publish_event(&outbox, &live.config.channels.meaningful_events, &identity, event.event_type(), event.payload());
+            // 보정이 끝나면 다음 세션에서 재사용할 수 있도록 사용자별로 저장합니다.
+            // (Redis가 느려도 이 연결의 메시지 처리가 멈추지 않도록 별도 작업에서 저장합니다.)
+            if let EngineEvent::CalibrationCompleted { calibration, .. } = &event {
+                tokio::spawn(save_calibration(redis.clone(), live.config.channels.calibration_key_prefix.clone(), identity.user_id.clone(), *calibration));
+            }
+            // 상태/점수 메시지는 클라이언트가 해당 기능을 요청한 경우에만 보냅니다.
+            let wants = |capability: &str| negotiated.as_ref().is_some_and(|n| n.has(capability));
+            if let EngineEvent::StateChanged { to, .. } = &event { if wants(CAP_STATE) { send_message(&mut write, &state_message(*to)).await; } }
+            if let Some(alarm) = alarm_message(&event, engine.state(), &live.config.alarms, &mut last_alarms, &live.alarm_catalog, &language) { send_message(&mut write, &alarm).await; }
+            if let EngineEvent::AttentionScore { score, components } = &event {
+                if wants(CAP_SCORE) { send_message(&mut write, &OutboundMessage::Score { score: *score, components: *components, timestamp: Utc::now().to_rfc3339() }).await; }
             }
         }
     }
-    println!("🔌 '{}' 와의 연결이 종료되었습니다.", addr);
+
+    // 연결이 끝나면 세션을 정리합니다. (close_session) 그사이 다른 연결이 세션을 넘겨받았으면, 이 연결은 아무것도 발행하지 않고 엔진만 넘겨줍니다.
+    if let Some(identity) = session.filter(|_| !handed_over) {
+        let disconnect_reason = (!session_ended).then_some(disconnect_reason);
+        if let Some(engine) = close_session(&session_store, &outbox, &live.config.channels.meaningful_events, &identity, connection_id, engine, disconnect_reason) {
+            match takeover_rx.try_recv() {
+                Ok(handover) => { let _ = handover.send(engine); log!("🔁 세션을 새 연결에 넘겨주었습니다: sessionId={}", identity.session_id); },
+                Err(_) => eprintln!("🔴 다른 연결이 가져간 세션의 엔진을 넘겨줄 곳이 없습니다: sessionId={}", identity.session_id),
+            }
+        }
+    }
+    log!("🔌 '{}' 와의 연결이 종료되었습니다.", addr);
+}
+
+
+// --- 세션 시작/재접속 처리 ---
+// 'start' 이벤트로 세션을 연결에 묶습니다. 재접속한 세션이면 끊기기 전의 분석 엔진과 끊겨 있던 시간(ms)을 돌려줍니다.
+// 기존 연결이 아직 끊긴 줄 모르고 열려 있으면(다음 Ping 전까지), 그 연결에서 엔진을 넘겨받고 기존 연결은 닫습니다.
+async fn claim_session(session_store: &SessionStore, identity: &SessionIdentity, connection_id: u64, takeover: mpsc::Sender<Handover>) -> Result<Option<(AttentionEngine, u64)>, ProtocolError> {
+    match session_store.claim(identity, connection_id, takeover) {
+        Claim::InUse => Err(ProtocolError::new("SESSION_IN_USE", format!("session '{}' belongs to another user", identity.session_id))),
+        Claim::New => Ok(None),
+        Claim::Resumed(parked) => Ok(Some((parked.engine, now_ms().saturating_sub(parked.disconnected_at_ms)))),
+        Claim::TakenOver(handover) => match tokio::time::timeout(Duration::from_secs(5), handover).await {
+            Ok(Ok(previous)) => Ok(Some((previous, 0))),
+            _ => { eprintln!("🔴 기존 연결에서 세션을 넘겨받지 못해 새로 시작합니다: sessionId={}", identity.session_id); Ok(None) },
+        },
+    }
+}
+
+// 재접속한 세션은 끊기기 전의 분석 엔진(상태, 누적 횟수, 보정 결과)을 그대로 이어서 사용하고, SESSION_RECONNECTED를 발행합니다.
+fn resume_session(engine: &mut AttentionEngine, previous: AttentionEngine, disconnected_ms: u64, live: &LiveSettings, outbox: &Outbox, identity: &SessionIdentity, client_msg: &ClientMessage) {
+    *engine = previous;
+    engine.reconfigure(live.config.analysis.engine_config()); // 끊겨 있는 동안 설정이 다시 읽혔을 수 있으므로 현재 설정을 적용합니다.
+    engine.resume(now_ms()); // 끊길 때 정산해 둔 상태를 재접속 시각부터 다시 엽니다.
+    log!("🔁 세션 재접속: sessionId={} ({}ms 만에 복귀)", identity.session_id, disconnected_ms);
+    create_and_publish_event(outbox, &live.config.channels.meaningful_events, identity, "SESSION_RECONNECTED", json!({ "disconnectedMs": disconnected_ms, "state": engine.state().as_str(), "client": client_msg.payload }));
+}
+
+// 새 세션은 이 사용자의 이전 보정 결과가 있으면 재사용하고(CALIBRATION_RESTORED), 없으면 새로 보정 단계를 시작합니다.
+async fn restore_calibration(engine: &mut AttentionEngine, redis: &SharedRedis, live: &LiveSettings, outbox: &Outbox, identity: &SessionIdentity) -> Vec<EngineEvent> {
+    match load_calibration(redis, &live.config.channels.calibration_key_prefix, &identity.user_id).await {
+        Some(calibration) => {
+            engine.apply_calibration(calibration);
+            create_and_publish_event(outbox, &live.config.channels.meaningful_events, identity, "CALIBRATION_RESTORED", json!({ "calibration": calibration, "thresholds": engine.thresholds() }));
+            Vec::new()
+        },
+        None => engine.start_calibration(now_ms()),
+    }
+}
+
+
+// --- 연결 종료 시 세션 정리 ---
+// 'end'로 끝난 세션(disconnect_reason이 None)은 연결과의 묶음만 풉니다.
+// 'end' 없이 연결이 끊긴 경우에는 열려 있던 상태를 끊긴 시각까지로 정산하고, 마지막 상태와 함께 CONNECTION_LOST를 발행한 뒤 재접속에 대비해 세션을 보관합니다.
+// (끊겨 있던 시간은 상태 지속 시간에 넣지 않습니다. 보관 기간 안에 돌아오지 않으면 만료 시 SESSION_END를 발행합니다.)
+// 서버 종료로 끊긴 경우에는 보관할 곳이 없으므로, 상태를 정산하고 SESSION_SUSPENDED를 발행합니다.
+// 그사이 다른 연결이 세션을 가져갔으면 아무것도 발행하지 않고, 넘겨줄 엔진을 그대로 돌려줍니다.
+fn close_session(session_store: &SessionStore, outbox: &Outbox, channel: &str, identity: &SessionIdentity, connection_id: u64, mut engine: AttentionEngine, disconnect_reason: Option<&str>) -> Option<AttentionEngine> {
+    let now = now_ms();
+    let Some(reason) = disconnect_reason else {
+        return (!session_store.release(identity, connection_id)).then_some(engine);
+    };
+    let announce_lost = |engine: &mut AttentionEngine| {
+        let payload = json!({ "reason": reason, "state": engine.state().as_str(), "stateDurationMs": now.saturating_sub(engine.state_since_ms()) });
+        for event in engine.suspend(now) { create_and_publish_event(outbox, channel, identity, event.event_type(), event.payload()); }
+        create_and_publish_event(outbox, channel, identity, "CONNECTION_LOST", payload);
+    };
+    if reason == "server_shutdown" {
+        if !session_store.release(identity, connection_id) { return Some(engine); }
+        announce_lost(&mut engine);
+        suspend_session(outbox, channel, identity, &mut engine, now, 0);
+        return None;
+    }
+    let unclaimed = session_store.park(identity.clone(), connection_id, engine, now, announce_lost);
+    if unclaimed.is_none() { log!("⏸️ 세션을 재접속 대기 상태로 보관합니다: sessionId={} (사유: {})", identity.session_id, reason); }
+    unclaimed
+}
+
+
+// --- Redis 연결 ---
+// 공유 Redis 연결을 만들 때까지 간격을 늘려가며(최대 30초) 계속 시도합니다. 연결되면 모든 연결과 출력 대상이 같은 연결을 씁니다.
+async fn connect_redis(redis: SharedRedis, client: redis::Client, config: RedisConfig) {
+    let mut delay = Duration::from_secs(1);
+    log!("⏳ Redis에 연결하는 중입니다... ({})", config.url());
+    loop {
+        match config.connection_manager(client.clone()).await {
+            Ok(conn) => { let _ = redis.set(conn); log!("🟢 Redis에 연결되었습니다. ({})", config.url()); return; },
+            Err(e) => {
+                eprintln!("🔴 Redis connection failed, {}초 뒤에 다시 시도합니다: {:?}", delay.as_secs(), e);
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(Duration::from_secs(30));
+            },
+        }
+    }
+}
+
+
+// --- 끊긴 세션 만료 처리 ---
+// 보관 기간 안에 재접속하지 않은 세션은 SESSION_END(사유: timeout)를 발행한 뒤 버립니다. (마지막 상태는 끊길 때 이미 정산했습니다.)
+// (CONNECTION_LOST와 마찬가지로, 세션 보관소가 세션을 가진 연결이 없을 때만 만료 세션으로 돌려줍니다.)
+async fn expire_parked_sessions(session_store: Arc<SessionStore>, outbox: Outbox, settings: watch::Receiver<Arc<LiveSettings>>) {
+    let mut sweep = interval(Duration::from_secs(10));
+    loop {
+        sweep.tick().await;
+        let expired = session_store.expire();
+        if expired.is_empty() { continue; }
+        let channel = settings.borrow().config.channels.meaningful_events.clone();
+        for parked in expired {
+            log!("⌛ 재접속하지 않은 세션을 만료 처리합니다: sessionId={}", parked.identity.session_id);
+            let disconnected_ms = now_ms().saturating_sub(parked.disconnected_at_ms);
+            create_and_publish_event(&outbox, &channel, &parked.identity, "SESSION_END", json!({ "reason": "timeout", "disconnectedMs": disconnected_ms }));
+        }
+    }
+}
+
+
+// --- 서버 종료 시 세션 정리 ---
+// 서버가 내려가면서 끝나는 세션은, 아직 열려 있는 상태의 지속 시간을 정산하고(끊길 때 정산한 세션은 제외) SESSION_SUSPENDED(사유: server_shutdown)를 발행합니다.
+// SESSION_END와 달리, 사용자가 끝낸 것이 아니므로 클라이언트가 다시 접속하면 같은 sessionId로 이어서 기록할 수 있습니다.
+fn suspend_session(outbox: &Outbox, channel: &str, identity: &SessionIdentity, engine: &mut AttentionEngine, ended_at_ms: u64, disconnected_ms: u64) {
+    let state = engine.state().as_str();
+    for event in engine.finish(ended_at_ms) { create_and_publish_event(outbox, channel, identity, event.event_type(), event.payload()); }
+    create_and_publish_event(outbox, channel, identity, "SESSION_SUSPENDED", json!({ "reason": "server_shutdown", "state": state, "disconnectedMs": disconnected_ms }));
+    log!("💤 세션을 중단 처리했습니다: sessionId={}", identity.session_id);
+}
+
+// 재접속을 기다리며 보관 중이던 세션들을 모두 중단 처리합니다.
+fn suspend_parked_sessions(session_store: &SessionStore, outbox: &Outbox, channel: &str) {
+    for mut parked in session_store.drain() {
+        let disconnected_ms = now_ms().saturating_sub(parked.disconnected_at_ms);
+        suspend_session(outbox, channel, &parked.identity, &mut parked.engine, parked.disconnected_at_ms, disconnected_ms);
+    }
+}
+
+
+// --- 설정 리로드 ---
+// SIGHUP을 받으면 설정 파일과 환경 변수를 다시 읽고, 검증을 통과한 경우에만 모든 연결의 설정을 한꺼번에 교체합니다.
+// 잘못된 설정이면 로그만 남기고 기존 설정을 그대로 유지합니다.
+fn reload_settings(settings: &watch::Sender<Arc<LiveSettings>>) {
+    let config = match ServerConfig::load() {
+        Ok(config) => config,
+        Err(e) => { eprintln!("🔴 설정 리로드 실패, 기존 설정을 유지합니다:\n{}", e); return; },
+    };
+    let restart_required = config.restart_required(&settings.borrow().config);
+    if !restart_required.is_empty() { log!("🟡 다음 설정은 서버를 다시 시작해야 적용됩니다: {}", restart_required.join(", ")); }
+    let alarm_catalog = AlarmCatalog::load(config.alarms.catalog_dir.as_deref());
+    settings.send_replace(Arc::new(LiveSettings { config, alarm_catalog }));
+    log!("🔄 CONFIG_RELOADED: 분석 임계값과 알람 정책을 새 설정으로 교체했습니다. (연결 {}개)", settings.receiver_count().saturating_sub(1));
 }
 
 
 // --- 나머지 헬퍼(도우미) 함수들 ---
 // 이 섹션의 함수들은 반복되는 작업을 재사용하기 위해 만들어진 함수들입니다.
 
-// 표준화된 형식의 서버 이벤트를 생성하고 Redis의 특정 채널에 발행(Publish)하는 함수입니다.
-async fn create_and_publish_event(
-    redis_conn: &mut redis::aio::Connection,
-    original_msg: &ClientMessage,
+// 표준화된 형식의 서버 이벤트를 생성하고, 특정 채널로 출력 대상들에 보내도록 발송 대기열에 넣는 함수입니다.
+fn create_and_publish_event(
+    outbox: &Outbox,
+    channel: &str,
+    identity: &SessionIdentity,
     event_type: &str,
     payload: Value,
 ) {
     let event = ServerEvent {
-        session_id: &original_msg.session_id,
-        user_id: &original_msg.user_id,
+        session_id: &identity.session_id,
+        user_id: &identity.user_id,
         timestamp: Utc::now().to_rfc3339(),
         event_type,
         payload,
     };
     if let Ok(event_json) = serde_json::to_string(&event) {
-        println!("-> [발행] eventType: '{}'", event.event_type);
-        // "attention-meaningful-events" 채널로 이벤트 발행
-        if redis_conn.publish::<_, _, i64>("attention-meaningful-events", &event_json).await.is_err() {
-            eprintln!("🔴 Redis 발행 실패");
-        }
+        log!("-> [발행] eventType: '{}'", event.event_type);
+        // 설정된 채널(기본 "attention-meaningful-events")로 이벤트 발행 (실패하면 대기열이 보관했다가 다시 시도합니다.)
+        outbox.publish(channel, event_type, event_json);
+    }
+}
+
+// 엔진 이벤트 중 클라이언트에게 알람을 보내야 하는 것에 대해 알람 메시지를 만들어 반환하는 함수입니다.
+// 알람 정책에서 끈 알람과, 쿨다운 시간 안에 이미 보낸 종류의 알람은 보내지 않습니다. 알람 문구는 사용자의 언어에 맞게 카탈로그에서 가져옵니다.
+fn alarm_message(event: &EngineEvent, state: AttentionState, policy: &AlarmPolicy, last_alarms: &mut HashMap<AlarmCode, u64>, catalog: &AlarmCatalog, language: &str) -> Option<OutboundMessage> {
+    let (code, severity, args) = match event {
+        // 새로운 상태에 맞는 알람을 생성합니다.
+        EngineEvent::StateChanged { to: AttentionState::Drowsy, .. } => (AlarmCode::Drowsy, Severity::Critical, vec![]),
+        EngineEvent::StateChanged { to: AttentionState::Distracted(DistractionKind::LookingAway), .. } => (AlarmCode::Distracted, Severity::Warning, vec![]),
+        EngineEvent::StateChanged { to: AttentionState::Distracted(DistractionKind::LookingDown), .. } => (AlarmCode::LookingDown, Severity::Warning, vec![]),
+        EngineEvent::StateChanged { to: AttentionState::UserLeft, .. } => (AlarmCode::UserLeft, Severity::Info, vec![]),
+        EngineEvent::StateChanged { to: AttentionState::Inactive, .. } => (AlarmCode::Inactive, Severity::Info, vec![]),
+        // 하품은 설정된 횟수(기본 5회)마다 알람을 보냅니다.
+        EngineEvent::YawnDetected { count, .. } if count.is_multiple_of(policy.yawn_every) => (AlarmCode::YawnRepeated, Severity::Info, vec![("count", count.to_string())]),
+        _ => return None, // 알람을 보낼 필요 없는 이벤트
+    };
+    if !policy.enabled(code) { return None; }
+    let now = now_ms();
+    if last_alarms.get(&code).is_some_and(|sent| now.saturating_sub(*sent) < policy.cooldown_secs.saturating_mul(1000)) { return None; }
+    last_alarms.insert(code, now);
+    let message = catalog.message(language, code.key(), &args);
+    Some(OutboundMessage::Alarm { code, severity, message, state: state.as_str(), timestamp: Utc::now().to_rfc3339() })
+}
+
+// 확정된 상태 변경을 클라이언트에게 알리는 메시지를 만드는 함수입니다.
+fn state_message(state: AttentionState) -> OutboundMessage {
+    let distraction = match state { AttentionState::Distracted(kind) => Some(kind.as_str()), _ => None };
+    OutboundMessage::State { state: state.as_str(), distraction, timestamp: Utc::now().to_rfc3339() }
+}
+
+// 클라이언트 이벤트를 정상 처리했다는 응답 메시지를 만드는 함수입니다.
+fn ack_message(client_msg: &ClientMessage) -> OutboundMessage {
+    OutboundMessage::Ack { event_type: client_msg.event_type.clone(), timestamp: Utc::now().to_rfc3339() }
+}
+
+// 클라이언트 메시지를 처리할 수 없을 때의 에러 응답 메시지를 만드는 함수입니다. (메시지 봉투를 읽었다면 그 eventType도 담습니다.)
+fn error_message(error: ProtocolError, client_msg: Option<&ClientMessage>) -> OutboundMessage {
+    log!("⚠️ 클라이언트 메시지 거부 ({}): {}", error.code, error.message);
+    OutboundMessage::Error { code: error.code, message: error.message, event_type: client_msg.map(|m| m.event_type.clone()), timestamp: Utc::now().to_rfc3339() }
+}
+
+// 메시지가 이 연결의 세션에 속하는지 확인하는 함수입니다.
+// 'hello'는 세션과 무관하고, 'start'는 아직 세션이 없을 때만, 그 외의 이벤트는 같은 세션/사용자로 시작된 세션이 있을 때만 받습니다.
+fn check_session(session: Option<&SessionIdentity>, client_msg: &ClientMessage, client_event: &ClientEvent) -> Result<(), ProtocolError> {
+    match (session, client_event) {
+        (_, ClientEvent::Hello(_)) => Ok(()),
+        (None, ClientEvent::Start(_)) => Ok(()),
+        (Some(session), ClientEvent::Start(_)) => Err(ProtocolError::new("SESSION_ALREADY_STARTED", format!("this connection is already bound to session '{}'", session.session_id))),
+        (None, _) => Err(ProtocolError::new("SESSION_NOT_STARTED", "send 'start' before other events")),
+        (Some(session), _) if *session != SessionIdentity::of(client_msg) => {
+            Err(ProtocolError::new("SESSION_MISMATCH", format!("this connection is bound to session '{}' of user '{}'", session.session_id, session.user_id)))
+        },
+        (Some(_), _) => Ok(()),
     }
 }
 
-// 전체 랜드마크 해시맵에서, 필요한 인덱스의 랜드마크들만 효율적으로 뽑아서 벡터로 반환하는 함수입니다.
-fn get_landmarks_by_indices(map: &HashMap<u32, Landmark>, indices: &[u32]) -> Vec<Landmark> {
-    indices.iter().filter_map(|&i| map.get(&i).copied()).collect()
+// 메시지의 사용자를 확정하는 함수입니다. 인증된 연결이면 토큰의 사용자 ID를 쓰고, 메시지에 다른 userId가 적혀 있으면 거부합니다.
+fn bind_user(client_msg: &mut ClientMessage, authenticated: Option<&AuthenticatedUser>) -> Result<(), ProtocolError> {
+    match authenticated {
+        Some(user) if !client_msg.user_id.is_empty() && client_msg.user_id != user.user_id => {
+            Err(ProtocolError::new("USER_MISMATCH", format!("userId '{}' does not match the authenticated user", client_msg.user_id)))
+        },
+        Some(user) => { client_msg.user_id = user.user_id.clone(); Ok(()) },
+        None if client_msg.user_id.is_empty() => Err(ProtocolError::new("MALFORMED_MESSAGE", "missing field `userId`")),
+        None => Ok(()),
+    }
+}
+
+// 프로토콜 협상 결과를 알려주는 메시지를 만드는 함수입니다.
+fn welcome_message(negotiated: &Negotiated) -> OutboundMessage {
+    OutboundMessage::Welcome { protocol_version: negotiated.protocol_version, capabilities: negotiated.capabilities.clone(), timestamp: Utc::now().to_rfc3339() }
+}
+
+// 보정 결과를 Redis에서 읽고 쓸 때 기다리는 최대 시간입니다. (Redis가 느려도 세션 시작이 오래 멈추지 않도록)
+const CALIBRATION_TIMEOUT: Duration = Duration::from_secs(1);
+
+// 사용자별 보정 결과를 저장하는 Redis 키를 만드는 함수입니다. (예: "attention-calibration:<userId>")
+fn calibration_key(prefix: &str, user_id: &str) -> String { format!("{}:{}", prefix, user_id) }
+
+// 이전 세션에서 저장한 사용자의 보정 결과를 Redis에서 읽어오는 함수입니다. (Redis에 아직 연결되지 않았거나, 없거나 형식이 잘못되었으면 None)
+// Redis가 응답하지 않으면 CALIBRATION_TIMEOUT 뒤에 포기하고 None을 돌려줍니다. (새로 보정합니다.)
+async fn load_calibration(redis: &SharedRedis, key_prefix: &str, user_id: &str) -> Option<Calibration> {
+    let mut redis_conn = redis.get()?.clone();
+    let stored: Option<String> = match tokio::time::timeout(CALIBRATION_TIMEOUT, redis_conn.get(calibration_key(key_prefix, user_id))).await {
+        Ok(stored) => stored.ok()?,
+        Err(_) => { eprintln!("🔴 보정 결과를 제때 읽지 못해 새로 보정합니다. (userId: {})", user_id); return None },
+    };
+    serde_json::from_str(&stored?).ok()
 }
 
-// 클라이언트에게 웹소켓을 통해 알람 메시지를 전송하는 함수입니다.
-async fn send_alarm(write_half: &mut (impl SinkExt<Message, Error = tokio_tungstenite::tungstenite::Error> + Unpin), message: &str) {
-    println!("🚨 알람 전송! -> {}", message);
-    let _ = write_half.send(Message::Text(message.to_string())).await;
+// 보정 결과를 다음 세션에서 재사용할 수 있도록 Redis에 저장하는 함수입니다. (Redis에 아직 연결되지 않았거나 CALIBRATION_TIMEOUT 안에 응답하지 않으면 저장하지 않습니다.)
+async fn save_calibration(redis: SharedRedis, key_prefix: String, user_id: String, calibration: Calibration) {
+    let Some(mut redis_conn) = redis.get().cloned() else { eprintln!("🔴 Redis에 연결되지 않아 보정 결과를 저장하지 못했습니다. (userId: {})", user_id); return };
+    if let Ok(calibration_json) = serde_json::to_string(&calibration) {
+        let saved = tokio::time::timeout(CALIBRATION_TIMEOUT, redis_conn.set::<_, _, ()>(calibration_key(&key_prefix, &user_id), calibration_json)).await;
+        if !matches!(saved, Ok(Ok(()))) {
+            eprintln!("🔴 보정 결과 저장 실패 (userId: {})", user_id);
+        }
+    }
+}
+
+// 분석 엔진에 넘길 현재 시각을 밀리초 단위 유닉스 타임스탬프로 반환하는 함수입니다.
+fn now_ms() -> u64 { Utc::now().timestamp_millis() as u64 }
+
+// 클라이언트에게 웹소켓을 통해 구조화된 JSON 메시지(알람, 상태, 점수 등)를 전송하는 함수입니다.
+async fn send_message(write_half: &mut (impl SinkExt<Message, Error = tokio_tungstenite::tungstenite::Error> + Unpin), message: &OutboundMessage) {
+    if let OutboundMessage::Alarm { message: text, .. } = message { log!("🚨 알람 전송! -> {}", text); }
+    if let Ok(message_json) = serde_json::to_string(message) {
+        let _ = write_half.send(Message::Text(message_json)).await;
+    }
 }
@@ -0,0 +1,345 @@
+// --- 이벤트 발송 대기열 (Outbox) ---
+// 세션 이벤트를 출력 대상(sinks 모듈)에 바로 쓰지 않고 대기열에 넣은 뒤, 별도 작업이 순서대로 보냅니다.
+// 출력 대상이 응답하지 않거나(Redis 중단, 구독자 없음, 웹훅 실패 등) 실패하면 점점 간격을 늘려가며 다시 시도하고,
+// 그동안 쌓인 이벤트가 메모리 한도를 넘으면 디스크의 임시 파일(spill)로 옮겨 두었다가, 복구되면 쌓인 순서 그대로 다시 보냅니다.
+// 디스크에 남은 이벤트는 서버를 다시 시작해도 이어서 보냅니다.
+// 다시 보내도 성공할 수 없는 이벤트(웹훅의 4xx 응답 등)는 전달 불가 파일(dead letter)에 옮기고 다음 이벤트로 넘어갑니다.
+// 출력 대상이 여러 개면 대상마다 대기열과 작업을 따로 두어, 한 대상이 실패해도 다른 대상으로의 전송은 멈추지 않습니다. (팬아웃)
+use crate::config::DeliveryConfig;
+use crate::sinks::{EventSink, SinkError, SinkEvent};
+use futures_util::future::join_all; // 종료 시 모든 출력 대상의 작업이 끝나기를 기다립니다.
+use std::collections::VecDeque; // 발행을 기다리는 이벤트들 (앞쪽이 오래된 것)
+use std::fs::{self, File, OpenOptions}; // 임시 파일을 읽고 쓰기 위해 사용합니다.
+use std::io::{BufRead, BufReader, Write}; // 임시 파일을 한 줄씩 읽고 쓰기 위해 사용합니다.
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::sync::mpsc; // 연결 작업들 → 발송 작업으로 이벤트를 넘기는 채널입니다.
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
+
+// 메모리 한도를 넘은 이벤트를 쌓아두는 파일과, 다시 발행 중인 이벤트를 담은 파일의 이름입니다. (출력 대상별 디렉터리 안)
+const PENDING_FILE: &str = "pending.jsonl";
+const REPLAYING_FILE: &str = "replaying.jsonl";
+// 출력 대상이 거절해 다시 보내지 않는 이벤트를 남기는 파일의 이름입니다. (운영자가 확인한 뒤 직접 처리합니다.)
+const DEAD_LETTER_FILE: &str = "dead_letter.jsonl";
+
+// 발송 작업이 받는 명령입니다.
+enum Command {
+    Publish(SinkEvent),
+    Close, // 서버 종료: 남은 이벤트를 마지막으로 발행해 보고, 못 보낸 것은 디스크에 남깁니다.
+}
+
+// 연결 작업들이 이벤트를 넣는 대기열 핸들입니다. 복제해도 같은 대기열을 가리킵니다.
+#[derive(Clone)]
+pub struct Outbox {
+    senders: Vec<mpsc::UnboundedSender<Command>>, // 출력 대상별 발송 작업
+}
+
+impl Outbox {
+    // 출력 대상마다 발송 작업을 시작하고, 대기열 핸들과 (모든 작업이 끝나면 끝나는) 작업 핸들을 돌려줍니다.
+    // 이전 실행에서 디스크에 남은 이벤트가 있으면 먼저 발행합니다.
+    pub fn start(sinks: Vec<Box<dyn EventSink>>, config: DeliveryConfig) -> (Outbox, JoinHandle<()>) {
+        let (senders, workers): (Vec<_>, Vec<_>) = sinks.into_iter().map(|sink| {
+            let (sender, receiver) = mpsc::unbounded_channel();
+            (sender, tokio::spawn(Worker::new(sink, config.clone()).run(receiver)))
+        }).unzip();
+        (Outbox { senders }, tokio::spawn(async move { join_all(workers).await; }))
+    }
+
+    // 세션 이벤트를 대기열에 넣습니다. 실제 발행은 발송 작업이 순서대로 처리하며, 실패하면 보관했다가 다시 시도합니다.
+    pub fn publish(&self, channel: &str, event_type: &str, payload: String) {
+        self.send(SinkEvent { channel: channel.to_string(), event_type: event_type.to_string(), payload, durable: true });
+    }
+
+    // 잃어도 되는 원본 데이터(일시정지 중의 프레임 등)를 대기열에 넣습니다. 출력 대상이 실패 중이면 보관하지 않고 버립니다.
+    pub fn publish_best_effort(&self, channel: &str, event_type: &str, payload: String) {
+        self.send(SinkEvent { channel: channel.to_string(), event_type: event_type.to_string(), payload, durable: false });
+    }
+
+    fn send(&self, event: SinkEvent) {
+        for sender in &self.senders {
+            if sender.send(Command::Publish(event.clone())).is_err() { eprintln!("🔴 발송 대기열이 닫혀 이벤트를 버립니다. (channel: {})", event.channel); }
+        }
+    }
+
+    // 발송 작업에 종료를 알립니다. (작업 핸들을 기다리면 마무리가 끝납니다.)
+    pub fn close(&self) {
+        for sender in &self.senders { let _ = sender.send(Command::Close); }
+    }
+}
+
+// 출력 대상 하나의 대기열을 소유하고 실제로 보내는 작업입니다. 이벤트는 항상 replaying → pending 파일 → 메모리 순서(오래된 순)로 보냅니다.
+struct Worker {
+    sink: Box<dyn EventSink>,
+    config: DeliveryConfig,
+    spill_dir: PathBuf,              // 이 출력 대상의 디스크 보관 디렉터리 (<delivery.spill_dir>/<출력 대상 이름>)
+    replay: VecDeque<SinkEvent>,     // 디스크에서 읽어와 다시 발행 중인 이벤트 (replaying 파일의 내용)
+    memory: VecDeque<SinkEvent>,     // 메모리에 쌓인 이벤트
+    pending_bytes: u64,              // pending 파일의 크기
+    dropped: u64,                    // 디스크 한도를 넘거나 디스크에 쓸 수 없어 버린 이벤트 수
+}
+
+impl Worker {
+    fn new(sink: Box<dyn EventSink>, config: DeliveryConfig) -> Self {
+        let spill_dir = config.spill_dir.join(sink.name());
+        if let Err(e) = fs::create_dir_all(&spill_dir) { eprintln!("🔴 발송 대기열 디렉터리를 만들 수 없습니다 ({}): {}", spill_dir.display(), e); }
+        let pending_bytes = fs::metadata(spill_dir.join(PENDING_FILE)).map(|m| m.len()).unwrap_or(0);
+        let mut worker = Worker { sink, config, spill_dir, replay: VecDeque::new(), memory: VecDeque::new(), pending_bytes, dropped: 0 };
+        // 지난 실행에서 다시 발행하던 도중에 끝난 이벤트가 있으면 그것부터 이어서 발행합니다. (일부는 중복 발행될 수 있습니다.)
+        worker.replay = worker.read_spill(&worker.path(REPLAYING_FILE));
+        let backlog = worker.replay.len() as u64 + worker.pending_bytes;
+        if backlog > 0 { log!("📦 지난 실행에서 보내지 못한 이벤트를 이어서 보냅니다. ({})", worker.spill_dir.display()); }
+        worker
+    }
+
+    fn path(&self, name: &str) -> PathBuf { self.spill_dir.join(name) }
+
+    fn is_empty(&self) -> bool { self.replay.is_empty() && self.pending_bytes == 0 && self.memory.is_empty() }
+
+    async fn run(mut self, mut receiver: mpsc::UnboundedReceiver<Command>) {
+        let mut backoff: Option<Duration> = None; // 발행이 실패하고 있으면 다음 재시도까지의 대기 시간
+        loop {
+            // 밀린 이벤트가 있으면 (실패 중이면 대기 시간 뒤에) 하나씩 발행하고, 그동안 들어오는 이벤트는 계속 대기열에 넣습니다.
+            let delay = (!self.is_empty()).then(|| backoff.unwrap_or(Duration::ZERO));
+            tokio::select! {
+                command = receiver.recv() => match command {
+                    Some(Command::Publish(event)) if event.durable => self.push(event),
+                    Some(Command::Publish(event)) => if self.is_empty() { let _ = self.sink.send(&event).await; }, // 원본 데이터는 밀린 이벤트가 없을 때 한 번만 보내 봅니다.
+                    Some(Command::Close) | None => { self.close().await; return; },
+                },
+                _ = sleep(delay.unwrap_or_default()), if delay.is_some() => match self.deliver_next().await {
+                    Ok(()) => {
+                        if backoff.take().is_some() { log!("🟢 이벤트 전송이 복구되었습니다. ({}) 밀린 이벤트를 순서대로 보냅니다.", self.sink.name()); }
+                    },
+                    Err(e) => {
+                        let retry_max = Duration::from_millis(self.config.retry_max_ms);
+                        let next = backoff.map_or(Duration::from_millis(self.config.retry_initial_ms), |d| (d * 2).min(retry_max));
+                        if backoff.is_none() { eprintln!("🔴 이벤트 전송 실패 ({}), 대기열에 보관하고 다시 시도합니다: {}", self.sink.name(), e); }
+                        backoff = Some(next);
+                    },
+                },
+            }
+        }
+    }
+
+    // 새 이벤트를 메모리 대기열에 넣습니다. 메모리 한도를 넘으면 메모리의 이벤트를 모두 pending 파일 끝에 옮깁니다. (순서 유지)
+    fn push(&mut self, event: SinkEvent) {
+        self.memory.push_back(event);
+        if self.memory.len() > self.config.memory_capacity { self.spill_memory(); }
+    }
+
+    // 메모리의 이벤트를 pending 파일 끝에 옮깁니다. 디스크 한도를 넘는 이벤트는 버립니다.
+    // 파일을 열 수 없으면 메모리 한도를 넘는 오래된 이벤트를 버려, 메모리가 끝없이 늘어나지 않게 합니다.
+    fn spill_memory(&mut self) {
+        let mut file = match OpenOptions::new().create(true).append(true).open(self.path(PENDING_FILE)) {
+            Ok(file) => file,
+            Err(e) => {
+                let excess = self.memory.len().saturating_sub(self.config.memory_capacity);
+                let previous = self.dropped;
+                self.memory.drain(..excess);
+                self.dropped += excess as u64;
+                // 이벤트가 들어올 때마다 로그가 쌓이지 않도록, 처음 버릴 때와 1000개마다 알립니다.
+                if excess > 0 && (previous == 0 || previous / 1000 != self.dropped / 1000) {
+                    eprintln!("🔴 발송 대기열 파일을 열 수 없어 오래된 이벤트를 버립니다. (지금까지 {}개): {}", self.dropped, e);
+                }
+                return;
+            },
+        };
+        let first_spill = self.pending_bytes == 0;
+        while let Some(event) = self.memory.pop_front() {
+            let Ok(mut line) = serde_json::to_string(&event) else { continue };
+            line.push('\n');
+            if self.pending_bytes + line.len() as u64 > self.config.spill_max_bytes {
+                self.dropped += 1;
+                continue;
+            }
+            if let Err(e) = file.write_all(line.as_bytes()) { eprintln!("🔴 발송 대기열 파일 쓰기 실패: {}", e); self.dropped += 1; continue; }
+            self.pending_bytes += line.len() as u64;
+        }
+        if first_spill { log!("💾 발행하지 못한 이벤트가 메모리 한도를 넘어 디스크에 보관합니다. ({})", self.path(PENDING_FILE).display()); }
+        if self.dropped > 0 { eprintln!("🔴 디스크 보관 한도(spill_max_bytes)를 넘거나 디스크에 쓸 수 없어 지금까지 이벤트 {}개를 버렸습니다.", self.dropped); }
+    }
+
+    // 가장 오래된 이벤트 하나를 발행합니다. 성공하거나 출력 대상이 거절하면(전달 불가 파일로 옮김) 대기열에서 뺍니다.
+    async fn deliver_next(&mut self) -> Result<(), String> {
+        // 다시 발행 중인 이벤트가 다 떨어지면, pending 파일을 replaying 파일로 바꿔 읽어옵니다.
+        if self.replay.is_empty() && self.pending_bytes > 0 {
+            let (pending, replaying) = (self.path(PENDING_FILE), self.path(REPLAYING_FILE));
+            fs::rename(&pending, &replaying).map_err(|e| format!("발송 대기열 파일을 옮길 수 없습니다: {}", e))?;
+            self.pending_bytes = 0;
+            self.replay = self.read_spill(&replaying);
+        }
+        let event = self.replay.front().or(self.memory.front()).ok_or("empty")?;
+        match self.sink.send(event).await {
+            Ok(()) => {},
+            Err(SinkError::Retry(e)) => return Err(e),
+            Err(SinkError::Reject(e)) => self.dead_letter(event, &e),
+        }
+        if self.replay.pop_front().is_some() {
+            if self.replay.is_empty() { let _ = fs::remove_file(self.path(REPLAYING_FILE)); }
+        } else {
+            self.memory.pop_front();
+        }
+        Ok(())
+    }
+
+    // 서버 종료 시: 남은 이벤트를 발행할 수 있는 만큼 발행하고, 실패하면 메모리의 이벤트를 디스크에 남깁니다. (다음 실행에서 이어서 발행)
+    async fn close(&mut self) {
+        while !self.is_empty() {
+            if let Err(e) = self.deliver_next().await {
+                eprintln!("🔴 종료 전에 발행하지 못한 이벤트를 디스크에 보관합니다: {}", e);
+                break;
+            }
+        }
+        if !self.memory.is_empty() { self.spill_memory(); }
+    }
+
+    // 출력 대상이 거절한 이벤트를 전달 불가 파일 끝에 남깁니다. 파일에 쓸 수 없으면 로그만 남기고 버립니다.
+    fn dead_letter(&self, event: &SinkEvent, reason: &str) {
+        let path = self.path(DEAD_LETTER_FILE);
+        eprintln!("🔴 출력 대상({})이 이벤트를 거절해 다시 보내지 않습니다: {} -> {}", self.sink.name(), reason, path.display());
+        let written = serde_json::to_string(event).map_err(|e| e.to_string()).and_then(|line| {
+            let mut file = OpenOptions::new().create(true).append(true).open(&path).map_err(|e| e.to_string())?;
+            writeln!(file, "{}", line).map_err(|e| e.to_string())
+        });
+        if let Err(e) = written { eprintln!("🔴 전달 불가 파일에 쓸 수 없어 이벤트를 버립니다 ({}): {}", path.display(), e); }
+    }
+
+    // 임시 파일의 이벤트를 읽어옵니다. (파일이 없으면 빈 대기열, 읽을 수 없는 줄은 건너뜁니다.)
+    fn read_spill(&self, path: &PathBuf) -> VecDeque<SinkEvent> {
+        let Ok(file) = File::open(path) else { return VecDeque::new() };
+        BufReader::new(file).lines().map_while(Result::ok).filter_map(|line| serde_json::from_str(&line).ok()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::future::BoxFuture;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    // online이 false인 동안은 실패하고, 보낸 이벤트의 payload를 순서대로 기록하는 출력 대상입니다. payload가 reject와 같은 이벤트는 거절합니다.
+    #[derive(Clone, Default)]
+    struct TestSink { online: Arc<AtomicBool>, sent: Arc<Mutex<Vec<String>>>, reject: Option<String> }
+
+    impl EventSink for TestSink {
+        fn name(&self) -> &'static str { "test" }
+
+        fn send<'a>(&'a mut self, event: &'a SinkEvent) -> BoxFuture<'a, Result<(), SinkError>> {
+            Box::pin(async move {
+                if !self.online.load(Ordering::SeqCst) { return Err(SinkError::Retry("offline".to_string())); }
+                if self.reject.as_ref() == Some(&event.payload) { return Err(SinkError::Reject("rejected".to_string())); }
+                self.sent.lock().unwrap().push(event.payload.clone());
+                Ok(())
+            })
+        }
+    }
+
+    fn config(name: &str) -> DeliveryConfig {
+        let spill_dir = std::env::temp_dir().join(format!("outbox-test-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&spill_dir);
+        DeliveryConfig { memory_capacity: 2, spill_dir, retry_initial_ms: 1, retry_max_ms: 5, ..DeliveryConfig::default() }
+    }
+
+    fn publish(outbox: &Outbox, range: std::ops::Range<usize>) {
+        for i in range { outbox.publish("events", "TEST", i.to_string()); }
+    }
+
+    fn numbers(range: std::ops::Range<usize>) -> Vec<String> { range.map(|i| i.to_string()).collect() }
+
+    // 발송 작업이 메모리 한도를 넘은 이벤트를 디스크에 옮길 때까지 기다립니다. (재시도하면서 pending 파일이 replaying 파일로 바뀔 수 있습니다.)
+    async fn wait_for_spill(config: &DeliveryConfig) {
+        let dir = config.spill_dir.join("test");
+        for _ in 0..500 {
+            if [PENDING_FILE, REPLAYING_FILE].iter().any(|name| dir.join(name).exists()) { return; }
+            sleep(Duration::from_millis(2)).await;
+        }
+        panic!("events were not spilled to {}", dir.display());
+    }
+
+    #[tokio::test]
+    async fn spilled_events_are_delivered_before_newer_ones() {
+        let config = config("order");
+        let sink = TestSink::default();
+        let (outbox, worker) = Outbox::start(vec![Box::new(sink.clone())], config.clone());
+        publish(&outbox, 0..5);
+        wait_for_spill(&config).await;
+        publish(&outbox, 5..7); // 디스크에 보관된 이벤트보다 늦게 들어온 이벤트는 메모리에 남습니다.
+        sink.online.store(true, Ordering::SeqCst);
+        outbox.close();
+        worker.await.unwrap();
+        assert_eq!(*sink.sent.lock().unwrap(), numbers(0..7));
+        let _ = fs::remove_dir_all(&config.spill_dir);
+    }
+
+    #[tokio::test]
+    async fn undelivered_events_resume_after_restart() {
+        let config = config("restart");
+        let offline = TestSink::default();
+        let (outbox, worker) = Outbox::start(vec![Box::new(offline.clone())], config.clone());
+        publish(&outbox, 0..5);
+        outbox.close(); // 보내지 못한 이벤트는 디스크에 남깁니다.
+        worker.await.unwrap();
+        assert!(offline.sent.lock().unwrap().is_empty());
+
+        let online = TestSink::default();
+        online.online.store(true, Ordering::SeqCst);
+        let (outbox, worker) = Outbox::start(vec![Box::new(online.clone())], config.clone());
+        publish(&outbox, 5..6);
+        outbox.close();
+        worker.await.unwrap();
+        assert_eq!(*online.sent.lock().unwrap(), numbers(0..6));
+        let _ = fs::remove_dir_all(&config.spill_dir);
+    }
+
+    #[tokio::test]
+    async fn best_effort_events_are_not_kept() {
+        let config = config("best-effort");
+        let offline = TestSink::default();
+        let (outbox, worker) = Outbox::start(vec![Box::new(offline.clone())], config.clone());
+        publish(&outbox, 0..1);
+        outbox.publish_best_effort("events", "RAW", "raw".to_string());
+        publish(&outbox, 1..2);
+        outbox.close();
+        worker.await.unwrap();
+
+        let online = TestSink::default();
+        online.online.store(true, Ordering::SeqCst);
+        let (outbox, worker) = Outbox::start(vec![Box::new(online.clone())], config.clone());
+        outbox.close();
+        worker.await.unwrap();
+        assert_eq!(*online.sent.lock().unwrap(), numbers(0..2));
+        let _ = fs::remove_dir_all(&config.spill_dir);
+    }
+
+    #[tokio::test]
+    async fn rejected_events_go_to_dead_letter_without_blocking() {
+        let config = config("reject");
+        let sink = TestSink { reject: Some("1".to_string()), ..TestSink::default() };
+        sink.online.store(true, Ordering::SeqCst);
+        let (outbox, worker) = Outbox::start(vec![Box::new(sink.clone())], config.clone());
+        publish(&outbox, 0..3);
+        outbox.close();
+        worker.await.unwrap();
+        assert_eq!(*sink.sent.lock().unwrap(), ["0", "2"]);
+        let dead = fs::read_to_string(config.spill_dir.join("test").join(DEAD_LETTER_FILE)).unwrap();
+        let dead: Vec<SinkEvent> = dead.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(dead.iter().map(|event| event.payload.as_str()).collect::<Vec<_>>(), ["1"]);
+        let _ = fs::remove_dir_all(&config.spill_dir);
+    }
+
+    #[test]
+    fn memory_stays_bounded_when_spill_file_cannot_be_opened() {
+        // 디스크 보관 디렉터리 자리에 파일이 있으면 pending 파일을 열 수 없습니다.
+        let config = config("unwritable");
+        fs::write(&config.spill_dir, "").unwrap();
+        let mut worker = Worker::new(Box::new(TestSink::default()), config.clone());
+        for i in 0..5 {
+            worker.push(SinkEvent { channel: "events".to_string(), event_type: "TEST".to_string(), payload: i.to_string(), durable: true });
+        }
+        assert_eq!(worker.memory.iter().map(|event| event.payload.clone()).collect::<Vec<_>>(), numbers(3..5));
+        assert_eq!(worker.dropped, 3);
+        let _ = fs::remove_file(&config.spill_dir);
+    }
+}
@@ -0,0 +1,262 @@
+// --- 클라이언트 ↔ 서버 메시지 프로토콜 ---
+// 클라이언트가 보내는 메시지(봉투 + 이벤트별 payload)와 서버가 보내는 메시지의 형식, 그리고 프로토콜 버전 협상을 정의합니다.
+// 클라이언트는 연결 직후 'hello'(또는 'start')로 사용하는 프로토콜 버전과 원하는 기능(capability)을 알리고,
+// 서버는 'welcome'으로 합의된 버전과 기능을 돌려줍니다. 형식이 잘못되었거나 알 수 없는 메시지에는 'error'로 응답합니다.
+// 'binaryFrames' 기능을 합의한 클라이언트는 'data' 이벤트 대신 랜드마크 좌표만 담은 바이너리 프레임을 보낼 수 있습니다.
+use serde::{Deserialize, Serialize}; // JSON 데이터를 Rust 구조체로 자동 변환하거나, 그 반대의 작업을 수행합니다.
+use serde_json::{json, Value}; // payload를 이벤트 종류별 구조체로 변환하기 전까지 유연하게 다루기 위해 사용합니다.
+use websocket::features::Landmark; // 클라이언트가 보내는 랜드마크 데이터 구조입니다.
+use websocket::score::ScoreComponents; // 종합 집중도 점수의 구성 요소입니다.
+
+// 서버가 이해하는 프로토콜 버전 목록입니다. 마지막 값이 현재 버전입니다.
+pub const SUPPORTED_PROTOCOL_VERSIONS: [u32; 1] = [1];
+
+// 서버가 제공하는 선택 기능입니다. 클라이언트가 요청한 기능 중 이 목록에 있는 것만 켜집니다. (알람과 응답/에러는 항상 전송)
+pub const CAP_STATE: &str = "state"; // 확정된 상태 변경을 'state' 메시지로 전송
+pub const CAP_SCORE: &str = "score"; // 종합 집중도 점수를 'score' 메시지로 전송
+pub const CAP_BINARY_FRAMES: &str = "binaryFrames"; // 'data' 이벤트 대신 바이너리 랜드마크 프레임 수신 (landmarkIndices 필요)
+pub const SERVER_CAPABILITIES: [&str; 3] = [CAP_STATE, CAP_SCORE, CAP_BINARY_FRAMES];
+
+// 바이너리 프레임의 첫 바이트로, 프레임 종류를 나타냅니다.
+// 랜드마크 프레임: [0x01] 다음에 협상한 landmarkIndices 순서대로 랜드마크마다 x, y, z를 little-endian f32로 이어 붙입니다. (랜드마크당 12바이트)
+pub const LANDMARK_FRAME: u8 = 0x01;
+const LANDMARK_FRAME_STRIDE: usize = 12;
+// 협상할 수 있는 랜드마크 인덱스의 최대 개수입니다. (MediaPipe Face Mesh의 전체 랜드마크 수)
+const MAX_FRAME_LANDMARKS: usize = 478;
+
+// 클라이언트가 보낼 수 있는 이벤트 종류입니다. (ClientEvent의 각 variant 이름과 같습니다.)
+const EVENT_TYPES: [&str; 6] = ["hello", "start", "data", "status_update", "calibrate", "end"];
+
+// --- 클라이언트 → 서버 ---
+
+// 클라이언트로부터 받는 모든 메시지의 봉투(envelope)입니다. `serde(rename = ...)`는 JSON의 키 이름과 Rust 변수 이름을 매핑합니다.
+// payload는 원본 그대로 Redis에 기록할 수 있도록 보관하고, 이벤트 종류별 검증은 ClientEvent::parse에서 합니다.
+#[derive(Deserialize, Debug, Clone)]
+pub struct ClientMessage {
+    #[serde(rename = "sessionId")]
+    pub session_id: String,
+    #[serde(rename = "userId", default)]
+    pub user_id: String, // 인증된 연결에서는 생략할 수 있습니다. (토큰의 사용자 ID 사용)
+    #[serde(rename = "eventType")]
+    pub event_type: String,
+    #[serde(default)]
+    pub payload: Value,
+}
+
+impl ClientMessage {
+    // 받은 텍스트(JSON)를 메시지 봉투로 파싱합니다.
+    pub fn parse(text: &str) -> Result<Self, ProtocolError> {
+        serde_json::from_str(text).map_err(|e| ProtocolError::new("MALFORMED_MESSAGE", e.to_string()))
+    }
+}
+
+// 이벤트 종류별로 payload의 형식이 정해진 클라이언트 이벤트입니다.
+#[derive(Deserialize, Debug)]
+#[serde(tag = "eventType", content = "payload", rename_all = "snake_case")]
+pub enum ClientEvent {
+    Hello(HelloPayload),
+    Start(StartPayload),
+    Data(DataPayload),
+    StatusUpdate(StatusPayload),
+    Calibrate(CalibratePayload),
+    End(EndPayload),
+}
+
+impl ClientEvent {
+    // 메시지 봉투의 eventType과 payload를 검증하여 이벤트로 변환합니다. payload가 없으면 빈 객체로 봅니다.
+    pub fn parse(message: &ClientMessage) -> Result<Self, ProtocolError> {
+        if !EVENT_TYPES.contains(&message.event_type.as_str()) {
+            return Err(ProtocolError::new("UNKNOWN_EVENT_TYPE", format!("unknown eventType '{}', expected one of {:?}", message.event_type, EVENT_TYPES)));
+        }
+        let payload = if message.payload.is_null() { json!({}) } else { message.payload.clone() };
+        serde_json::from_value(json!({ "eventType": message.event_type, "payload": payload }))
+            .map_err(|e| ProtocolError::new("INVALID_PAYLOAD", format!("invalid payload for '{}': {}", message.event_type, e)))
+    }
+}
+
+// 'hello': 사용하는 프로토콜 버전과 원하는 기능을 알립니다.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct HelloPayload {
+    pub protocol_version: u32,
+    pub capabilities: Option<Vec<String>>, // 생략하면 서버가 제공하는 모든 기능을 켭니다.
+    pub landmark_indices: Option<Vec<u32>>, // 바이너리 프레임에 담을 랜드마크 인덱스와 그 순서
+}
+
+// 'start': 세션을 시작합니다. 'hello' 없이 바로 시작하는 클라이언트는 여기에 프로토콜 버전을 적습니다.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct StartPayload {
+    pub protocol_version: Option<u32>,
+    pub capabilities: Option<Vec<String>>,
+    pub landmark_indices: Option<Vec<u32>>,
+    pub language: Option<String>, // 알람 문구 언어 (예: "en-US")
+}
+
+// 'data': 한 프레임의 랜드마크 목록 전체를 담습니다.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct DataPayload { pub landmarks: Vec<Landmark> }
+
+// 'status_update': 클라이언트의 특정 상태(얼굴 미감지, 일시정지 등)를 전달합니다.
+#[derive(Deserialize, Debug)]
+pub struct StatusPayload { pub status: String }
+
+// 'calibrate': 재보정을 요청합니다. (payload 없음)
+#[derive(Deserialize, Debug)]
+pub struct CalibratePayload {}
+
+// 'end': 세션을 종료합니다.
+#[derive(Deserialize, Debug)]
+pub struct EndPayload { pub reason: Option<String> }
+
+// 클라이언트 메시지를 처리할 수 없는 이유입니다. 'error' 메시지로 클라이언트에게 그대로 전달됩니다.
+#[derive(Debug, Clone)]
+pub struct ProtocolError {
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl ProtocolError {
+    pub fn new(code: &'static str, message: impl Into<String>) -> Self { ProtocolError { code, message: message.into() } }
+}
+
+// --- 프로토콜 버전/기능 협상 ---
+
+// 한 연결에서 합의된 프로토콜 버전과 기능입니다.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Negotiated {
+    pub protocol_version: u32,
+    pub capabilities: Vec<&'static str>,
+    pub landmark_indices: Vec<u32>, // 바이너리 프레임의 랜드마크 순서 (binaryFrames를 합의하지 않았으면 비어 있음)
+}
+
+impl Negotiated {
+    // 해당 기능이 켜져 있는지 확인합니다.
+    pub fn has(&self, capability: &str) -> bool { self.capabilities.contains(&capability) }
+}
+
+// 클라이언트가 알린 버전과 기능으로 협상합니다. 버전이 없거나 지원하지 않는 버전이면 에러를 반환합니다.
+// binaryFrames는 랜드마크 인덱스 목록을 함께 알린 경우에만 켜집니다.
+pub fn negotiate(protocol_version: Option<u32>, requested: Option<&[String]>, landmark_indices: Option<&[u32]>) -> Result<Negotiated, ProtocolError> {
+    let protocol_version = protocol_version.ok_or_else(|| ProtocolError::new("PROTOCOL_VERSION_REQUIRED", "declare protocolVersion in 'hello' or 'start'"))?;
+    if !SUPPORTED_PROTOCOL_VERSIONS.contains(&protocol_version) {
+        return Err(ProtocolError::new("UNSUPPORTED_PROTOCOL_VERSION", format!("protocol version {} is not supported (supported: {:?})", protocol_version, SUPPORTED_PROTOCOL_VERSIONS)));
+    }
+    let landmark_indices = landmark_indices.unwrap_or_default().to_vec();
+    if landmark_indices.len() > MAX_FRAME_LANDMARKS {
+        return Err(ProtocolError::new("INVALID_PAYLOAD", format!("landmarkIndices may list at most {} landmarks", MAX_FRAME_LANDMARKS)));
+    }
+    let mut capabilities: Vec<&'static str> = match requested {
+        Some(requested) => SERVER_CAPABILITIES.into_iter().filter(|cap| requested.iter().any(|r| r == cap)).collect(),
+        None => SERVER_CAPABILITIES.to_vec(),
+    };
+    if landmark_indices.is_empty() { capabilities.retain(|cap| *cap != CAP_BINARY_FRAMES); }
+    let landmark_indices = if capabilities.contains(&CAP_BINARY_FRAMES) { landmark_indices } else { Vec::new() };
+    Ok(Negotiated { protocol_version, capabilities, landmark_indices })
+}
+
+// 바이너리 랜드마크 프레임을 협상한 인덱스 순서에 따라 'data' payload로 변환합니다.
+pub fn decode_landmark_frame(bytes: &[u8], landmark_indices: &[u32]) -> Result<DataPayload, ProtocolError> {
+    let (kind, body) = bytes.split_first().ok_or_else(|| ProtocolError::new("MALFORMED_FRAME", "empty binary frame"))?;
+    if *kind != LANDMARK_FRAME {
+        return Err(ProtocolError::new("MALFORMED_FRAME", format!("unknown binary frame kind 0x{:02x}", kind)));
+    }
+    if body.len() != landmark_indices.len() * LANDMARK_FRAME_STRIDE {
+        return Err(ProtocolError::new("MALFORMED_FRAME", format!("expected {} bytes of landmarks for {} negotiated indices, got {}", landmark_indices.len() * LANDMARK_FRAME_STRIDE, landmark_indices.len(), body.len())));
+    }
+    let read_f32 = |chunk: &[u8]| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]) as f64;
+    let landmarks = landmark_indices.iter().zip(body.chunks_exact(LANDMARK_FRAME_STRIDE))
+        .map(|(&index, chunk)| Landmark { index, x: read_f32(&chunk[0..4]), y: read_f32(&chunk[4..8]), z: read_f32(&chunk[8..12]) })
+        .collect();
+    Ok(DataPayload { landmarks })
+}
+
+// --- 서버 → 클라이언트 ---
+
+// 서버가 웹소켓으로 클라이언트에게 보내는 메시지의 형식입니다. `type` 필드로 메시지 종류를 구분합니다.
+// 클라이언트는 code로 문구를 현지화하고, severity로 표시 방식을 정하고, state/score로 화면을 갱신할 수 있습니다.
+#[derive(Serialize, Debug)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum OutboundMessage {
+    // 프로토콜 협상 결과
+    Welcome { #[serde(rename = "protocolVersion")] protocol_version: u32, capabilities: Vec<&'static str>, timestamp: String },
+    // 사용자에게 보여줄 경고 알람
+    Alarm { code: AlarmCode, severity: Severity, message: String, state: &'static str, timestamp: String },
+    // 확정된 집중도 상태 변경
+    State { state: &'static str, #[serde(skip_serializing_if = "Option::is_none")] distraction: Option<&'static str>, timestamp: String },
+    // 주기적인 종합 집중도 점수
+    Score { score: f64, components: ScoreComponents, timestamp: String },
+    // 클라이언트 이벤트(start, end 등)를 정상적으로 처리했다는 응답
+    Ack { #[serde(rename = "eventType")] event_type: String, timestamp: String },
+    // 클라이언트 메시지를 처리할 수 없을 때의 에러 응답 (어떤 이벤트에 대한 에러인지 알 수 있으면 eventType도 담습니다.)
+    Error { code: &'static str, message: String, #[serde(rename = "eventType", skip_serializing_if = "Option::is_none")] event_type: Option<String>, timestamp: String },
+}
+
+// 알람의 종류를 나타내는 코드입니다. 클라이언트는 이 코드를 키로 문구를 현지화할 수 있습니다.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AlarmCode { Drowsy, Distracted, LookingDown, UserLeft, Inactive, YawnRepeated }
+
+impl AlarmCode {
+    // 모든 알람 코드입니다. (설정 검증 등에 사용)
+    pub const ALL: [AlarmCode; 6] = [AlarmCode::Drowsy, AlarmCode::Distracted, AlarmCode::LookingDown, AlarmCode::UserLeft, AlarmCode::Inactive, AlarmCode::YawnRepeated];
+
+    // 알람 문구 카탈로그에서 사용하는 키입니다. (직렬화된 code 값과 같습니다.)
+    pub fn key(&self) -> &'static str {
+        match self {
+            AlarmCode::Drowsy => "DROWSY",
+            AlarmCode::Distracted => "DISTRACTED",
+            AlarmCode::LookingDown => "LOOKING_DOWN",
+            AlarmCode::UserLeft => "USER_LEFT",
+            AlarmCode::Inactive => "INACTIVE",
+            AlarmCode::YawnRepeated => "YAWN_REPEATED",
+        }
+    }
+}
+
+// 알람의 심각도입니다. 클라이언트는 이 값에 따라 알람의 색상이나 소리를 다르게 표시할 수 있습니다.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity { Info, Warning, Critical }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 랜드마크마다 (x, y, z)를 little-endian f32로 이어 붙인 바이너리 프레임을 만듭니다.
+    fn encode(points: &[(f32, f32, f32)]) -> Vec<u8> {
+        let mut bytes = vec![LANDMARK_FRAME];
+        for (x, y, z) in points { for v in [x, y, z] { bytes.extend_from_slice(&v.to_le_bytes()); } }
+        bytes
+    }
+
+    fn decode_error(bytes: &[u8], landmark_indices: &[u32]) -> String {
+        let error = decode_landmark_frame(bytes, landmark_indices).unwrap_err();
+        assert_eq!(error.code, "MALFORMED_FRAME");
+        error.message
+    }
+
+    #[test]
+    fn decodes_landmarks_in_negotiated_order() {
+        let payload = decode_landmark_frame(&encode(&[(0.25, 0.5, -0.125), (1.0, 2.0, 3.0)]), &[33, 10]).unwrap();
+        let decoded: Vec<_> = payload.landmarks.iter().map(|lm| (lm.index, lm.x, lm.y, lm.z)).collect();
+        assert_eq!(decoded, vec![(33, 0.25, 0.5, -0.125), (10, 1.0, 2.0, 3.0)]);
+    }
+
+    #[test]
+    fn rejects_empty_and_unknown_frames() {
+        assert_eq!(decode_error(&[], &[1]), "empty binary frame");
+        let mut bytes = encode(&[(0.0, 0.0, 0.0)]);
+        bytes[0] = 0x02;
+        assert_eq!(decode_error(&bytes, &[1]), "unknown binary frame kind 0x02");
+    }
+
+    #[test]
+    fn rejects_frames_that_do_not_match_negotiated_indices() {
+        let bytes = encode(&[(0.0, 0.0, 0.0), (1.0, 1.0, 1.0)]);
+        assert_eq!(decode_error(&bytes, &[1, 2, 3]), "expected 36 bytes of landmarks for 3 negotiated indices, got 24");
+        assert_eq!(decode_error(&bytes, &[1]), "expected 12 bytes of landmarks for 1 negotiated indices, got 24");
+        assert_eq!(decode_error(&bytes[..bytes.len() - 1], &[1, 2]), "expected 24 bytes of landmarks for 2 negotiated indices, got 23");
+        assert!(decode_landmark_frame(&[LANDMARK_FRAME], &[]).unwrap().landmarks.is_empty());
+    }
+}
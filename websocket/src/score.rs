@@ -0,0 +1,188 @@
+// --- 종합 집중도 점수 ---
+// 이산적인 상태(집중/졸음/분산 등)만으로는 대시보드에 흐름을 보여주기 어려우므로,
+// EAR, MAR, 고개 회전, 깜빡임 빈도, 현재 상태 지속 시간을 가중 평균하여 0~100점의 연속적인 점수를 일정 주기마다 계산합니다.
+use crate::engine::{AttentionState, Thresholds};
+use serde::{Deserialize, Serialize}; // 점수 구성 요소를 이벤트 payload에 담고, 가중치를 설정 파일에서 읽기 위해 사용합니다.
+use std::time::Duration; // 점수 발행 주기 설정에 사용합니다.
+
+// 각 구성 요소가 종합 점수에 반영되는 가중치입니다. (합이 1이 아니어도 자동으로 정규화됩니다.)
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScoreWeights {
+    pub ear: f64,   // 눈 개방 정도
+    pub mar: f64,   // 입 개방 정도 (하품, 잡담)
+    pub yaw: f64,   // 정면 응시 정도
+    pub blink: f64, // 깜빡임 빈도의 정상 범위 여부
+    pub state: f64, // 현재 상태와 그 지속 시간
+}
+
+impl Default for ScoreWeights {
+    fn default() -> Self {
+        ScoreWeights { ear: 0.3, mar: 0.1, yaw: 0.25, blink: 0.1, state: 0.25 }
+    }
+}
+
+// 점수 계산 설정입니다.
+#[derive(Debug, Clone, Copy)]
+pub struct ScoreConfig {
+    pub interval: Duration,              // ATTENTION_SCORE 이벤트를 발행하는 주기
+    pub weights: ScoreWeights,
+    pub normal_blink_rate: (f64, f64),   // 정상으로 보는 분당 깜빡임 횟수 범위 (이보다 적으면 눈 피로/과몰입, 많으면 피로)
+    pub state_ramp: Duration,            // 상태 점수가 최고점(집중) 또는 최저점(졸음/분산)에 도달하기까지 걸리는 시간
+}
+
+impl Default for ScoreConfig {
+    fn default() -> Self {
+        ScoreConfig { interval: Duration::from_secs(5), weights: ScoreWeights::default(), normal_blink_rate: (8.0, 25.0), state_ramp: Duration::from_secs(60) }
+    }
+}
+
+// 종합 점수를 이루는 구성 요소별 점수입니다. (각각 0.0 ~ 1.0)
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct ScoreComponents {
+    pub ear: f64,
+    pub mar: f64,
+    pub yaw: f64,
+    pub blink: Option<f64>, // 깜빡임 빈도를 잴 수 없으면(프레임이 드물면) None이고, 종합 점수에서 빠집니다.
+    pub state: f64,
+}
+
+// 한 주기 동안의 프레임 특징 값을 모아 두었다가 점수를 계산하는 누적기입니다.
+#[derive(Debug, Clone)]
+pub struct ScoreAccumulator {
+    config: ScoreConfig,
+    last_emit_ms: u64,  // 마지막으로 점수를 발행한 시각
+    ear_sum: f64,
+    mar_sum: f64,
+    yaw_dev_sum: f64,   // 기준 방향과의 고개 회전 차이의 합
+    frames: u32,
+}
+
+impl ScoreAccumulator {
+    pub fn new(config: ScoreConfig, start_ms: u64) -> Self {
+        ScoreAccumulator { config, last_emit_ms: start_ms, ear_sum: 0.0, mar_sum: 0.0, yaw_dev_sum: 0.0, frames: 0 }
+    }
+
+    // 누적 중인 값은 유지한 채 설정만 바꿉니다. (설정 리로드)
+    pub fn set_config(&mut self, config: ScoreConfig) { self.config = config; }
+
+    // 한 프레임의 특징 값을 누적합니다.
+    pub fn add(&mut self, ear: f64, mar: f64, yaw_deviation: f64) {
+        self.ear_sum += ear;
+        self.mar_sum += mar;
+        self.yaw_dev_sum += yaw_deviation.abs();
+        self.frames += 1;
+    }
+
+    // 발행 주기가 지났고 누적된 프레임이 있으면 (종합 점수, 구성 요소)를 계산하고 누적값을 초기화합니다.
+    pub fn take(&mut self, now_ms: u64, thresholds: &Thresholds, blink_rate_per_min: Option<f64>, state: AttentionState, time_in_state: Duration) -> Option<(f64, ScoreComponents)> {
+        if now_ms.saturating_sub(self.last_emit_ms) < self.config.interval.as_millis() as u64 || self.frames == 0 { return None; }
+        let n = self.frames as f64;
+        let components = ScoreComponents {
+            ear: clamp01((self.ear_sum / n - thresholds.ear_enter) / (thresholds.ear_exit - thresholds.ear_enter).max(f64::EPSILON)),
+            mar: 1.0 - clamp01(self.mar_sum / n / thresholds.mar),
+            yaw: 1.0 - clamp01(self.yaw_dev_sum / n / thresholds.yaw_enter),
+            blink: blink_rate_per_min.map(|rate| self.blink_score(rate)),
+            state: self.state_score(state, time_in_state),
+        };
+        let weights = self.config.weights;
+        let blink_weight = if components.blink.is_some() { weights.blink } else { 0.0 };
+        let total_weight = weights.ear + weights.mar + weights.yaw + blink_weight + weights.state;
+        let weighted = components.ear * weights.ear + components.mar * weights.mar + components.yaw * weights.yaw + components.blink.unwrap_or(0.0) * blink_weight + components.state * weights.state;
+        let score = if total_weight > 0.0 { 100.0 * weighted / total_weight } else { 0.0 };
+
+        self.last_emit_ms = now_ms;
+        self.ear_sum = 0.0;
+        self.mar_sum = 0.0;
+        self.yaw_dev_sum = 0.0;
+        self.frames = 0;
+        Some((score, components))
+    }
+
+    // 분당 깜빡임 횟수가 정상 범위 안이면 1점, 범위를 벗어날수록 0점에 가까워집니다.
+    fn blink_score(&self, rate: f64) -> f64 {
+        let (low, high) = self.config.normal_blink_rate;
+        if rate < low { clamp01(rate / low) } else if rate > high { clamp01(1.0 - (rate - high) / high) } else { 1.0 }
+    }
+
+    // 집중 상태가 길어질수록 점수가 오르고, 졸음/분산 상태가 길어질수록 점수가 내려갑니다.
+    fn state_score(&self, state: AttentionState, time_in_state: Duration) -> f64 {
+        let progress = clamp01(time_in_state.as_secs_f64() / self.config.state_ramp.as_secs_f64().max(f64::EPSILON));
+        match state {
+            AttentionState::Focused => 0.5 + 0.5 * progress,
+            AttentionState::Drowsy | AttentionState::Distracted(_) => 0.5 - 0.5 * progress,
+            AttentionState::UserLeft | AttentionState::Inactive | AttentionState::Paused => 0.0,
+        }
+    }
+}
+
+// 값을 0.0 ~ 1.0 범위로 자르는 함수입니다.
+fn clamp01(value: f64) -> f64 { if value.is_nan() { 0.0 } else { value.clamp(0.0, 1.0) } }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    fn assert_close(actual: f64, expected: f64) { assert!((actual - expected).abs() < 1e-9, "{} != {}", actual, expected); }
+
+    // 눈을 충분히 뜨고, 입을 다물고, 정면을 보는 프레임 하나를 누적한 누적기입니다.
+    fn attentive(config: ScoreConfig) -> ScoreAccumulator {
+        let mut score = ScoreAccumulator::new(config, 0);
+        score.add(0.3, 0.0, 0.0);
+        score
+    }
+
+    #[test]
+    fn waits_for_interval_and_frames() {
+        let thresholds = Thresholds::default();
+        let mut score = ScoreAccumulator::new(ScoreConfig::default(), 0);
+        assert!(score.take(10_000, &thresholds, None, AttentionState::Focused, MINUTE).is_none()); // 누적된 프레임이 없습니다.
+        score.add(0.3, 0.0, 0.0);
+        assert!(score.take(4_999, &thresholds, None, AttentionState::Focused, MINUTE).is_none()); // 주기(5초) 전입니다.
+        assert!(score.take(5_000, &thresholds, None, AttentionState::Focused, MINUTE).is_some());
+        assert!(score.take(20_000, &thresholds, None, AttentionState::Focused, MINUTE).is_none()); // 발행하면 누적값을 비웁니다.
+    }
+
+    #[test]
+    fn components_are_weighted_and_normalized() {
+        let thresholds = Thresholds::default();
+        let (score, components) = attentive(ScoreConfig::default()).take(5_000, &thresholds, Some(15.0), AttentionState::Focused, MINUTE).unwrap();
+        assert_eq!(components, ScoreComponents { ear: 1.0, mar: 1.0, yaw: 1.0, blink: Some(1.0), state: 1.0 });
+        assert_close(score, 100.0);
+
+        // 가중치의 합이 1이 아니어도 정규화됩니다. (EAR만 반영: 진입/해제 임계값의 중간이면 50점)
+        let weights = ScoreWeights { ear: 2.0, mar: 0.0, yaw: 0.0, blink: 0.0, state: 0.0 };
+        let mut score = ScoreAccumulator::new(ScoreConfig { weights, ..ScoreConfig::default() }, 0);
+        score.add(0.225, 0.0, 0.0);
+        let (score, components) = score.take(5_000, &thresholds, Some(15.0), AttentionState::Drowsy, MINUTE).unwrap();
+        assert_close(components.ear, 0.5);
+        assert_close(score, 50.0);
+    }
+
+    #[test]
+    fn unmeasurable_blink_rate_is_left_out() {
+        let thresholds = Thresholds::default();
+        let config = ScoreConfig::default();
+        // 깜빡이지 않으면 깜빡임 점수는 0점이고 종합 점수가 그만큼(가중치 0.1) 내려갑니다.
+        let (score, components) = attentive(config).take(5_000, &thresholds, Some(0.0), AttentionState::Focused, MINUTE).unwrap();
+        assert_eq!(components.blink, Some(0.0));
+        assert_close(score, 90.0);
+        // 깜빡임을 잴 수 없으면 깜빡임 점수 없이 나머지 구성 요소로만 계산합니다.
+        let (score, components) = attentive(config).take(5_000, &thresholds, None, AttentionState::Focused, MINUTE).unwrap();
+        assert_eq!(components.blink, None);
+        assert_close(score, 100.0);
+    }
+
+    #[test]
+    fn state_score_ramps_with_time_in_state() {
+        let thresholds = Thresholds::default();
+        let state = |state, seconds| attentive(ScoreConfig::default()).take(5_000, &thresholds, None, state, Duration::from_secs(seconds)).unwrap().1.state;
+        assert_close(state(AttentionState::Focused, 0), 0.5);
+        assert_close(state(AttentionState::Focused, 30), 0.75);
+        assert_close(state(AttentionState::Drowsy, 30), 0.25);
+        assert_close(state(AttentionState::Drowsy, 600), 0.0);
+        assert_close(state(AttentionState::Paused, 0), 0.0);
+    }
+}
@@ -0,0 +1,201 @@
+// --- 세션 보관소 (재접속 시 세션 이어가기) ---
+// 네트워크가 잠깐 끊겨 클라이언트가 다시 접속하면, 새 연결에서 분석 엔진을 처음부터 다시 만드는 대신
+// 끊긴 연결이 쓰던 엔진(상태 머신, 누적 횟수, 보정 결과)을 sessionId로 찾아 이어서 사용합니다.
+// 끊긴 세션은 일정 시간(TTL) 동안만 보관하고, 그 안에 돌아오지 않은 세션은 만료 처리합니다.
+// 끊긴 줄 모르고 아직 열려 있는 연결의 세션도 추적하여, 같은 세션으로 다시 접속하면 그 연결에서 엔진을 넘겨받습니다.
+use crate::protocol::ClientMessage;
+use std::collections::HashMap; // sessionId → 연결 중인 세션 / 보관 중인 세션
+use std::sync::atomic::{AtomicU64, Ordering}; // 연결마다 구분 번호를 붙입니다.
+use std::sync::Mutex; // 여러 연결 작업이 함께 접근하므로 잠금으로 보호합니다. (잠근 채로 await하지 않습니다.)
+use std::time::{Duration, Instant}; // 보관 기간(TTL) 계산에 사용합니다.
+use tokio::sync::{mpsc, oneshot}; // 세션을 넘겨받을 연결이 기존 연결에 엔진을 달라고 요청하고 받는 채널입니다.
+use websocket::engine::AttentionEngine;
+
+// 'start' 이벤트로 연결에 묶인 세션과 사용자입니다. 이 연결에서 발행하는 모든 이벤트는 이 세션/사용자의 것으로 기록됩니다.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionIdentity { pub session_id: String, pub user_id: String }
+
+impl SessionIdentity {
+    pub fn of(client_msg: &ClientMessage) -> Self { SessionIdentity { session_id: client_msg.session_id.clone(), user_id: client_msg.user_id.clone() } }
+}
+
+// 세션을 넘겨받을 연결이 기존 연결에 보내는 요청입니다. 기존 연결은 이 채널로 자기 엔진을 넘겨주고 끝납니다.
+pub type Handover = oneshot::Sender<AttentionEngine>;
+
+// 연결이 끊긴 뒤 재접속을 기다리며 보관 중인 세션입니다.
+pub struct ParkedSession {
+    pub identity: SessionIdentity,
+    pub engine: AttentionEngine, // 끊기기 직전까지의 분석 엔진
+    pub disconnected_at_ms: u64, // 연결이 끊긴 시각 (이벤트 기록용)
+    parked_at: Instant,          // 보관을 시작한 시각 (만료 계산용)
+}
+
+// 지금 어떤 연결에 묶여 있는 세션입니다.
+struct LiveSession {
+    identity: SessionIdentity,
+    connection_id: u64,                 // 세션을 가진 연결
+    takeover: mpsc::Sender<Handover>,   // 그 연결에 세션을 넘겨달라고 요청하는 채널
+}
+
+// 'start'로 세션을 연결에 묶은 결과입니다.
+pub enum Claim {
+    New,                                         // 처음 시작하는 세션
+    Resumed(Box<ParkedSession>),                 // 보관 중이던 세션을 이어서 사용
+    TakenOver(oneshot::Receiver<AttentionEngine>), // 아직 열려 있는 기존 연결에서 넘겨받는 세션 (기존 연결은 엔진을 넘겨주고 닫힙니다.)
+    InUse,                                       // 다른 사용자가 쓰고 있는 sessionId
+}
+
+#[derive(Default)]
+struct Sessions {
+    live: HashMap<String, LiveSession>,
+    parked: HashMap<String, ParkedSession>,
+}
+
+// 연결 중인 세션과 끊긴 세션들을 sessionId로 관리하는 저장소입니다.
+// 같은 sessionId는 항상 한 곳(연결 중 또는 보관 중)에만 있으며, 세션을 가진 연결만 세션을 보관하거나 끝낼 수 있습니다.
+pub struct SessionStore {
+    ttl: Duration,
+    next_connection_id: AtomicU64,
+    sessions: Mutex<Sessions>,
+}
+
+impl SessionStore {
+    pub fn new(ttl: Duration) -> Self {
+        SessionStore { ttl, next_connection_id: AtomicU64::new(1), sessions: Mutex::new(Sessions::default()) }
+    }
+
+    // 새 연결에 붙일 구분 번호를 돌려줍니다.
+    pub fn connection_id(&self) -> u64 { self.next_connection_id.fetch_add(1, Ordering::Relaxed) }
+
+    // 세션을 연결에 묶습니다. 같은 사용자의 세션이 보관 중이면 꺼내 주고, 다른 연결에 아직 묶여 있으면 그 연결에 넘겨달라고 요청합니다.
+    // (재접속한 클라이언트보다 끊긴 연결을 늦게 알아채는 경우, 예: 다음 Ping 때까지)
+    pub fn claim(&self, identity: &SessionIdentity, connection_id: u64, takeover: mpsc::Sender<Handover>) -> Claim {
+        let mut sessions = self.sessions.lock().unwrap();
+        let owner = sessions.live.get(&identity.session_id).map(|live| &live.identity).or(sessions.parked.get(&identity.session_id).map(|parked| &parked.identity));
+        if owner.is_some_and(|owner| owner != identity) { return Claim::InUse; }
+        let previous = sessions.live.insert(identity.session_id.clone(), LiveSession { identity: identity.clone(), connection_id, takeover });
+        if let Some(parked) = sessions.parked.remove(&identity.session_id) { return Claim::Resumed(Box::new(parked)); }
+        let Some(previous) = previous else { return Claim::New };
+        let (handover, engine) = oneshot::channel();
+        if previous.takeover.try_send(handover).is_err() { return Claim::New; } // 기존 연결이 이미 끝났으면 새로 시작합니다.
+        Claim::TakenOver(engine)
+    }
+
+    // 세션이 끝났을 때('end', 서버 종료) 연결과의 묶음을 풉니다. 이미 다른 연결이 넘겨받았으면 아무것도 하지 않고 false를 돌려줍니다.
+    pub fn release(&self, identity: &SessionIdentity, connection_id: u64) -> bool {
+        let mut sessions = self.sessions.lock().unwrap();
+        let owned = sessions.live.get(&identity.session_id).is_some_and(|live| live.connection_id == connection_id);
+        if owned { sessions.live.remove(&identity.session_id); }
+        owned
+    }
+
+    // 끊긴 연결의 세션을 보관합니다. 이 연결이 아직 세션을 가지고 있을 때만 보관하고, announce(끊김 이벤트 발행)를 같은 잠금 안에서 먼저 실행합니다.
+    // (그래야 곧바로 재접속한 연결의 이벤트가 끊김 이벤트보다 먼저 기록되지 않습니다.)
+    // 다른 연결이 이미 넘겨받았으면 보관하지 않고 엔진을 그대로 돌려줍니다.
+    pub fn park(&self, identity: SessionIdentity, connection_id: u64, mut engine: AttentionEngine, disconnected_at_ms: u64, announce: impl FnOnce(&mut AttentionEngine)) -> Option<AttentionEngine> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let owned = sessions.live.get(&identity.session_id).is_some_and(|live| live.connection_id == connection_id);
+        if !owned || sessions.parked.contains_key(&identity.session_id) { return Some(engine); }
+        sessions.live.remove(&identity.session_id);
+        announce(&mut engine);
+        let session = ParkedSession { identity: identity.clone(), engine, disconnected_at_ms, parked_at: Instant::now() };
+        sessions.parked.insert(identity.session_id, session);
+        None
+    }
+
+    // 보관 중인 세션을 모두 꺼내서 돌려줍니다. (서버 종료 시 정리용)
+    pub fn drain(&self) -> Vec<ParkedSession> {
+        self.sessions.lock().unwrap().parked.drain().map(|(_, session)| session).collect()
+    }
+
+    // 보관 기간이 지난 세션들을 꺼내서 돌려줍니다. 그사이 다른 연결에 묶인 세션은 만료하지 않습니다.
+    pub fn expire(&self) -> Vec<ParkedSession> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let Sessions { live, parked } = &mut *sessions;
+        let expired: Vec<String> = parked.iter().filter(|(id, s)| s.parked_at.elapsed() >= self.ttl && !live.contains_key(*id)).map(|(id, _)| id.clone()).collect();
+        expired.iter().filter_map(|id| parked.remove(id)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use websocket::engine::EngineConfig;
+
+    fn identity(user_id: &str) -> SessionIdentity { SessionIdentity { session_id: "session".to_string(), user_id: user_id.to_string() } }
+
+    fn engine(start_ms: u64) -> AttentionEngine { AttentionEngine::new(EngineConfig::default(), start_ms) }
+
+    // 세션을 넘겨달라는 요청을 받는 연결 쪽 채널을 만듭니다.
+    fn takeover() -> (mpsc::Sender<Handover>, mpsc::Receiver<Handover>) { mpsc::channel(1) }
+
+    #[test]
+    fn session_belongs_to_its_first_user() {
+        let store = SessionStore::new(Duration::from_secs(60));
+        let (tx, _rx) = takeover();
+        assert!(matches!(store.claim(&identity("alice"), 1, tx.clone()), Claim::New));
+        assert!(matches!(store.claim(&identity("mallory"), 2, tx.clone()), Claim::InUse));
+        // 보관 중인 세션도 다른 사용자는 가져갈 수 없습니다.
+        assert!(store.park(identity("alice"), 1, engine(0), 1000, |_| {}).is_none());
+        assert!(matches!(store.claim(&identity("mallory"), 2, tx), Claim::InUse));
+    }
+
+    #[test]
+    fn parked_session_resumes_on_reconnect() {
+        let store = SessionStore::new(Duration::from_secs(60));
+        let (tx, _rx) = takeover();
+        store.claim(&identity("alice"), 1, tx.clone());
+        let mut announced = false;
+        assert!(store.park(identity("alice"), 1, engine(500), 1000, |_| announced = true).is_none());
+        assert!(announced);
+        let Claim::Resumed(parked) = store.claim(&identity("alice"), 2, tx) else { panic!("expected a resumed session") };
+        assert_eq!((parked.disconnected_at_ms, parked.engine.state_since_ms()), (1000, 500));
+        // 재접속한 세션은 만료되지 않습니다.
+        assert!(store.expire().is_empty());
+    }
+
+    #[tokio::test]
+    async fn reconnect_takes_over_from_open_connection() {
+        let store = SessionStore::new(Duration::from_secs(60));
+        let (old_tx, mut old_rx) = takeover();
+        let (new_tx, _new_rx) = takeover();
+        store.claim(&identity("alice"), 1, old_tx);
+        let Claim::TakenOver(receiver) = store.claim(&identity("alice"), 2, new_tx) else { panic!("expected a takeover") };
+        // 기존 연결은 요청을 받아 엔진을 넘겨줍니다.
+        old_rx.recv().await.unwrap().send(engine(500)).ok().unwrap();
+        assert_eq!(receiver.await.unwrap().state_since_ms(), 500);
+        // 넘겨준 뒤에 끊긴 기존 연결은 세션을 보관하거나 끝낼 수 없습니다.
+        let mut announced = false;
+        assert!(store.park(identity("alice"), 1, engine(0), 1000, |_| announced = true).is_some());
+        assert!(!announced);
+        assert!(!store.release(&identity("alice"), 1));
+        assert!(store.release(&identity("alice"), 2));
+    }
+
+    #[test]
+    fn closed_connection_cannot_hand_over() {
+        let store = SessionStore::new(Duration::from_secs(60));
+        let (old_tx, old_rx) = takeover();
+        let (new_tx, _new_rx) = takeover();
+        store.claim(&identity("alice"), 1, old_tx);
+        drop(old_rx); // 기존 연결이 이미 끝났습니다.
+        assert!(matches!(store.claim(&identity("alice"), 2, new_tx), Claim::New));
+    }
+
+    #[test]
+    fn expire_returns_only_unclaimed_parked_sessions() {
+        let store = SessionStore::new(Duration::ZERO);
+        let (tx, _rx) = takeover();
+        let other = SessionIdentity { session_id: "other".to_string(), user_id: "bob".to_string() };
+        store.claim(&identity("alice"), 1, tx.clone());
+        store.claim(&other, 2, tx.clone());
+        store.park(identity("alice"), 1, engine(0), 1000, |_| {});
+        store.park(other.clone(), 2, engine(0), 1000, |_| {});
+        // 만료 전에 다시 접속한 세션은 만료하지 않습니다.
+        assert!(matches!(store.claim(&other, 3, tx), Claim::Resumed(_)));
+        let expired = store.expire();
+        assert_eq!(expired.iter().map(|parked| parked.identity.user_id.as_str()).collect::<Vec<_>>(), ["alice"]);
+        assert!(store.expire().is_empty());
+        assert!(store.drain().is_empty());
+    }
+}
@@ -0,0 +1,194 @@
+// --- 이벤트 출력 대상 (Sink) ---
+// 세션 이벤트를 어디에 쓸지 정하는 출력 대상들입니다. 발송 대기열(outbox)은 설정된 출력 대상마다 따로 대기열을 두고 같은 이벤트를 모두에 보냅니다. (팬아웃)
+// - redis: Redis Pub/Sub(PUBLISH) 또는 Redis Streams(XADD) (delivery.output)
+// - file: 한 줄에 이벤트 하나씩 JSON으로 파일 끝에 덧붙입니다. (NDJSON)
+// - stdout: 표준 출력에 이벤트 JSON을 한 줄씩 씁니다. (Redis 없이 로컬에서 실행할 때)
+// - webhook: 이벤트 JSON을 HTTP POST로 보냅니다.
+use crate::config::{DeliveryConfig, OutputMode, SinkKind};
+use futures_util::future::BoxFuture; // 트레이트 객체(dyn EventSink)에서 비동기 함수를 쓰기 위해 사용합니다.
+use redis::aio::ConnectionManager;
+use std::sync::Arc;
+use tokio::sync::OnceCell; // 서버가 시작된 뒤에 만들어지는 공유 Redis 연결을 담습니다.
+use redis::AsyncCommands; // PUBLISH 명령을 보내기 위해 사용합니다.
+use serde::{Deserialize, Serialize}; // 발송 대기열이 이벤트를 디스크에 한 줄짜리 JSON으로 보관합니다.
+use std::io::Write; // 표준 출력에 쓰기 실패를 전송 실패로 돌려주기 위해 사용합니다.
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt; // 파일에 비동기로 쓰기 위해 사용합니다.
+
+// 모든 연결과 출력 대상이 함께 쓰는 Redis 연결입니다. 서버는 Redis 없이도 시작하고, 백그라운드에서 연결되면 채워집니다.
+pub type SharedRedis = Arc<OnceCell<ConnectionManager>>;
+
+// 공유 Redis 연결의 핸들을 복제합니다. 아직 연결되지 않았으면 전송 실패로 보고, 발송 대기열이 이벤트를 보관했다가 다시 시도합니다.
+fn connected(redis: &SharedRedis) -> Result<ConnectionManager, String> { redis.get().cloned().ok_or_else(|| "redis not connected yet".to_string()) }
+
+// 출력 대상에 보내는 이벤트 하나입니다.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SinkEvent {
+    pub channel: String,    // Redis 채널(스트림) 이름. 다른 출력 대상은 이벤트 구분용으로만 사용합니다.
+    #[serde(default)]
+    pub event_type: String, // 이벤트 타입 (스트림 항목의 eventType 필드, 웹훅의 X-Event-Type 헤더)
+    pub payload: String,    // 이벤트 JSON
+    #[serde(default = "durable_default")]
+    pub durable: bool,      // true면 반드시 전달되어야 하는 세션 이벤트, false면 실패 시 버려도 되는 원본 데이터 (일시정지 중의 프레임 등)
+}
+
+fn durable_default() -> bool { true }
+
+// 출력 대상의 전송 실패입니다.
+#[derive(Debug)]
+pub enum SinkError {
+    Retry(String),  // 일시적인 실패: 발송 대기열이 보관했다가 같은 이벤트를 다시 보냅니다. (연결 끊김, 5xx 응답 등)
+    Reject(String), // 다시 보내도 성공할 수 없는 실패: 발송 대기열이 이 이벤트를 전달 불가 파일(dead letter)에 옮기고 다음 이벤트로 넘어갑니다. (4xx 응답 등)
+}
+
+impl From<String> for SinkError {
+    fn from(message: String) -> Self { SinkError::Retry(message) }
+}
+
+impl std::fmt::Display for SinkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SinkError::Retry(message) | SinkError::Reject(message) => f.write_str(message),
+        }
+    }
+}
+
+// 이벤트 출력 대상이 구현하는 트레이트입니다. 전송에 실패하면 Err를 돌려주고, 발송 대기열이 나중에 같은 이벤트로 다시 호출합니다.
+pub trait EventSink: Send {
+    // 로그와 디스크 보관 디렉터리 이름에 쓰는 출력 대상의 이름입니다. 출력 대상마다 달라야 디스크 보관 파일이 섞이지 않습니다.
+    fn name(&self) -> &'static str;
+
+    // 이벤트 하나를 보냅니다.
+    fn send<'a>(&'a mut self, event: &'a SinkEvent) -> BoxFuture<'a, Result<(), SinkError>>;
+}
+
+// Redis Pub/Sub 채널에 PUBLISH합니다.
+pub struct RedisPubSubSink {
+    redis: SharedRedis,
+    require_subscriber: bool, // true면 구독자가 하나도 없을 때 세션 이벤트를 전송 실패로 봅니다.
+}
+
+impl EventSink for RedisPubSubSink {
+    fn name(&self) -> &'static str { "redis-pubsub" }
+
+    fn send<'a>(&'a mut self, event: &'a SinkEvent) -> BoxFuture<'a, Result<(), SinkError>> {
+        Box::pin(async move {
+            let receivers: i64 = connected(&self.redis)?.publish(&event.channel, &event.payload).await.map_err(|e| e.to_string())?;
+            // 구독자(예: saver)가 없으면 아무도 받지 못한 것이므로, 설정에 따라 구독자가 생길 때까지 다시 시도합니다.
+            if receivers == 0 && self.require_subscriber && event.durable { return Err(format!("no subscriber on '{}'", event.channel).into()); }
+            Ok(())
+        })
+    }
+}
+
+// Redis Streams에 XADD합니다. 채널 이름을 스트림 키로 쓰고, eventType과 이벤트 JSON(data)을 필드로 저장하며, 오래된 항목은 MAXLEN으로 잘라냅니다.
+pub struct RedisStreamsSink {
+    redis: SharedRedis,
+    maxlen: usize,
+}
+
+impl EventSink for RedisStreamsSink {
+    fn name(&self) -> &'static str { "redis-streams" }
+
+    fn send<'a>(&'a mut self, event: &'a SinkEvent) -> BoxFuture<'a, Result<(), SinkError>> {
+        Box::pin(async move {
+            redis::cmd("XADD").arg(&event.channel).arg("MAXLEN").arg("~").arg(self.maxlen).arg("*")
+                .arg("eventType").arg(&event.event_type).arg("data").arg(&event.payload)
+                .query_async::<_, String>(&mut connected(&self.redis)?).await.map_err(|e| e.to_string())?;
+            Ok(())
+        })
+    }
+}
+
+// 이벤트 JSON을 파일 끝에 한 줄씩 덧붙입니다. (NDJSON) 파일은 처음 쓸 때 열고, 쓰기에 실패하면 다음 전송 때 다시 엽니다.
+pub struct FileSink {
+    path: PathBuf,
+    file: Option<tokio::fs::File>,
+}
+
+impl EventSink for FileSink {
+    fn name(&self) -> &'static str { "file" }
+
+    fn send<'a>(&'a mut self, event: &'a SinkEvent) -> BoxFuture<'a, Result<(), SinkError>> {
+        Box::pin(async move {
+            if self.file.is_none() {
+                let file = tokio::fs::OpenOptions::new().create(true).append(true).open(&self.path).await
+                    .map_err(|e| format!("{}: {}", self.path.display(), e))?;
+                self.file = Some(file);
+            }
+            let file = self.file.as_mut().expect("file opened above");
+            let line = format!("{}\n", event.payload);
+            // tokio의 파일 쓰기는 백그라운드에서 끝나므로, flush까지 기다려야 쓰기 실패가 이 이벤트의 실패로 보고됩니다.
+            let written = async { file.write_all(line.as_bytes()).await?; file.flush().await }.await;
+            if let Err(e) = written {
+                self.file = None;
+                return Err(format!("{}: {}", self.path.display(), e).into());
+            }
+            Ok(())
+        })
+    }
+}
+
+// 표준 출력에 이벤트 JSON을 한 줄씩 씁니다. 이 출력 대상이 켜져 있으면 서버 로그는 표준 에러로 나갑니다. (logging 모듈)
+pub struct StdoutSink;
+
+impl EventSink for StdoutSink {
+    fn name(&self) -> &'static str { "stdout" }
+
+    fn send<'a>(&'a mut self, event: &'a SinkEvent) -> BoxFuture<'a, Result<(), SinkError>> {
+        Box::pin(async move {
+            // println!은 파이프가 닫히면(EPIPE) 패닉하므로, 쓰기 실패를 전송 실패로 돌려줍니다.
+            writeln!(std::io::stdout().lock(), "{}", event.payload).map_err(|e| format!("stdout: {}", e).into())
+        })
+    }
+}
+
+// 이벤트 JSON을 HTTP POST로 보냅니다. 2xx 응답이 아니면 전송 실패로 보고, 4xx 응답은 다시 보내지 않습니다.
+pub struct WebhookSink {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl EventSink for WebhookSink {
+    fn name(&self) -> &'static str { "webhook" }
+
+    fn send<'a>(&'a mut self, event: &'a SinkEvent) -> BoxFuture<'a, Result<(), SinkError>> {
+        Box::pin(async move {
+            let response = self.client.post(&self.url)
+                .header("content-type", "application/json")
+                .header("x-event-type", &event.event_type)
+                .header("x-event-channel", &event.channel)
+                .body(event.payload.clone())
+                .send().await.map_err(|e| e.to_string())?;
+            let status = response.status();
+            if status.is_success() { return Ok(()); }
+            let error = format!("webhook responded {}", status);
+            // 4xx 응답은 같은 요청을 다시 보내도 거절되므로 다시 시도하지 않습니다. (시간 초과 408과 요청 제한 429는 제외)
+            let retryable = !status.is_client_error() || status == reqwest::StatusCode::REQUEST_TIMEOUT || status == reqwest::StatusCode::TOO_MANY_REQUESTS;
+            Err(if retryable { SinkError::Retry(error) } else { SinkError::Reject(error) })
+        })
+    }
+}
+
+// 설정된 출력 대상들을 만듭니다. redis 출력 대상은 공유 Redis 연결을 씁니다.
+pub fn build_sinks(config: &DeliveryConfig, redis: &SharedRedis) -> Result<Vec<Box<dyn EventSink>>, String> {
+    config.sinks.iter().map(|kind| -> Result<Box<dyn EventSink>, String> {
+        Ok(match kind {
+            SinkKind::Redis => {
+                let redis = redis.clone();
+                match config.output {
+                    OutputMode::PubSub => Box::new(RedisPubSubSink { redis, require_subscriber: config.require_subscriber }),
+                    OutputMode::Streams => Box::new(RedisStreamsSink { redis, maxlen: config.stream_maxlen }),
+                }
+            },
+            SinkKind::File => Box::new(FileSink { path: config.file_path.clone(), file: None }),
+            SinkKind::Stdout => Box::new(StdoutSink),
+            SinkKind::Webhook => {
+                let url = config.webhook_url.clone().ok_or("webhook 출력 대상에는 delivery.webhook_url이 필요합니다.")?;
+                let client = reqwest::Client::builder().timeout(Duration::from_secs(config.webhook_timeout_secs)).build().map_err(|e| e.to_string())?;
+                Box::new(WebhookSink { client, url })
+            },
+        })
+    }).collect()
+}
@@ -0,0 +1,111 @@
+// --- 하품 감지 ---
+// 프레임마다 MAR이 임계값을 넘는지만 세면 3초짜리 하품 한 번이 수십 번으로 집계되므로,
+// 입이 벌어진 시점(onset)부터 다시 닫힌 시점(offset)까지를 하나의 구간으로 묶어 하품 한 번으로 판정합니다.
+// 말을 할 때는 입이 짧게 여러 번 열리고 닫히므로, 최소 지속 시간보다 짧은 구간은 하품이 아닌 '말하기'로 보고 버립니다.
+use std::time::Duration; // 하품 지속 시간 설정에 사용합니다.
+
+// 하품 구간 판정 설정입니다.
+#[derive(Debug, Clone, Copy)]
+pub struct YawnConfig {
+    pub min_duration: Duration, // 입이 이 시간 이상 계속 크게 벌어져 있어야 하품으로 봅니다. (더 짧으면 말하기)
+    pub max_duration: Duration, // 이 시간보다 오래 벌어져 있으면 하품이 아닌 것으로 봅니다.
+    pub exit_ratio: f64,        // MAR이 (하품 임계값 × 이 비율) 아래로 내려가야 입이 닫힌 것으로 봅니다. (히스테리시스)
+}
+
+impl Default for YawnConfig {
+    fn default() -> Self {
+        // 클라이언트는 약 1초에 한 프레임을 보내므로, 2초 이상 연속으로 벌어진 경우를 하품으로 봅니다.
+        YawnConfig { min_duration: Duration::from_secs(2), max_duration: Duration::from_secs(10), exit_ratio: 0.8 }
+    }
+}
+
+// 하나의 하품 구간입니다.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct YawnEpisode {
+    pub started_ms: u64,  // 입이 벌어지기 시작한 시각 (onset)
+    pub duration_ms: u64, // 입이 다시 닫힐 때까지 걸린 시간
+    pub peak_mar: f64,    // 구간 중 가장 컸던 MAR 값
+}
+
+// 프레임별 MAR 값으로 하품 구간을 추적하는 감지기입니다.
+#[derive(Debug, Clone)]
+pub struct YawnDetector {
+    config: YawnConfig,
+    open_since: Option<(u64, f64)>, // 입이 벌어져 있다면, (벌어지기 시작한 시각, 지금까지의 최대 MAR)
+}
+
+impl YawnDetector {
+    pub fn new(config: YawnConfig) -> Self {
+        YawnDetector { config, open_since: None }
+    }
+
+    // 한 프레임의 MAR을 반영합니다. 입이 닫히는 순간 그 구간이 하품으로 인정되면 하품 구간을 반환합니다.
+    pub fn update(&mut self, mar: f64, mar_threshold: f64, timestamp_ms: u64) -> Option<YawnEpisode> {
+        match self.open_since {
+            None => {
+                if mar > mar_threshold { self.open_since = Some((timestamp_ms, mar)); }
+                None
+            },
+            Some((started_ms, peak_mar)) if mar >= mar_threshold * self.config.exit_ratio => {
+                self.open_since = Some((started_ms, peak_mar.max(mar)));
+                None
+            },
+            Some((started_ms, peak_mar)) => {
+                self.open_since = None;
+                let duration = Duration::from_millis(timestamp_ms.saturating_sub(started_ms));
+                let is_yawn = duration >= self.config.min_duration && duration <= self.config.max_duration;
+                is_yawn.then_some(YawnEpisode { started_ms, duration_ms: duration.as_millis() as u64, peak_mar })
+            },
+        }
+    }
+
+    // 진행 중인 구간을 버립니다. (일시정지 등으로 프레임 흐름이 끊겼을 때)
+    pub fn reset(&mut self) { self.open_since = None; }
+
+    // 진행 중인 구간은 유지한 채 설정만 바꿉니다. (설정 리로드)
+    pub fn set_config(&mut self, config: YawnConfig) { self.config = config; }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAR_THRESHOLD: f64 = 0.6;
+
+    // (시각, MAR) 프레임들을 차례로 넣고, 인정된 하품 구간들을 돌려줍니다.
+    fn episodes(frames: impl IntoIterator<Item = (u64, f64)>) -> Vec<YawnEpisode> {
+        let mut detector = YawnDetector::new(YawnConfig::default());
+        frames.into_iter().filter_map(|(ts, mar)| detector.update(mar, MAR_THRESHOLD, ts)).collect()
+    }
+
+    #[test]
+    fn yawn_spans_onset_to_offset() {
+        let frames = [(0, 0.1), (1000, 0.7), (2000, 0.9), (3000, 0.8), (4000, 0.1)];
+        assert_eq!(episodes(frames), vec![YawnEpisode { started_ms: 1000, duration_ms: 3000, peak_mar: 0.9 }]);
+    }
+
+    #[test]
+    fn short_openings_are_talking() {
+        assert_eq!(episodes([(0, 0.7), (1000, 0.1), (2000, 0.7), (3000, 0.1)]), vec![]);
+    }
+
+    #[test]
+    fn overlong_openings_are_not_yawns() {
+        assert_eq!(episodes((0..12).map(|i| (i * 1000, 0.7)).chain([(12_000, 0.1)])), vec![]);
+    }
+
+    #[test]
+    fn mouth_stays_open_above_exit_ratio() {
+        // 0.5는 임계값(0.6)보다 작지만 해제 기준(0.6 × 0.8)보다 크므로 같은 구간으로 이어집니다.
+        let frames = [(0, 0.7), (1000, 0.5), (2000, 0.5), (3000, 0.4)];
+        assert_eq!(episodes(frames), vec![YawnEpisode { started_ms: 0, duration_ms: 3000, peak_mar: 0.7 }]);
+    }
+
+    #[test]
+    fn reset_discards_open_episode() {
+        let mut detector = YawnDetector::new(YawnConfig::default());
+        detector.update(0.7, MAR_THRESHOLD, 0);
+        detector.reset();
+        assert_eq!(detector.update(0.1, MAR_THRESHOLD, 3000), None);
+    }
+}